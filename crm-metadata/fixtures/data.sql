@@ -0,0 +1,23 @@
+INSERT INTO publishers(id, name, avatar)
+VALUES (1, 'Acme Studios', 'https://placehold.co/400x400?text=acme'),
+       (2, 'Jane Doe', 'https://placehold.co/400x400?text=jane'),
+       (3, '李小龙工作室', 'https://placehold.co/400x400?text=li');
+
+INSERT INTO contents(id, name, description, url, image, type, created_at, views, likes, dislikes)
+VALUES (1, 'The Long Road', 'A journey across the continent', 'https://acme.org/contents/1',
+        'https://placehold.co/1600x900?text=1', 'movie', '2024-05-01 00:00:00+00', 1234567, 23456, 789),
+       (2, 'Morning Coffee', 'Five minutes to start the day', 'https://acme.org/contents/2',
+        'https://placehold.co/1600x900?text=2', 'short', '2024-06-15 08:30:00+00', 98765, 4321, 12),
+       (3, 'Behind the Scenes', 'How The Long Road was made', 'https://acme.org/contents/3',
+        'https://placehold.co/1600x900?text=3', 'vlog', '2024-07-20 12:00:00+00', 45678, 1234, 56),
+       (4, '功夫之路', 'A kung fu story', 'https://acme.org/contents/4',
+        'https://placehold.co/1600x900?text=4', 'movie', '2024-08-08 20:00:00+08', 3456789, 123456, 2345),
+       (5, 'Dream Machine', 'Made entirely by a model', 'https://acme.org/contents/5',
+        'https://placehold.co/1600x900?text=5', 'ai_generated', '2024-09-01 00:00:00+00', 5678, 345, 67);
+
+INSERT INTO content_publishers(content_id, publisher_id)
+VALUES (1, 1), (1, 2), (2, 2), (3, 1), (4, 3), (5, 1);
+
+-- ids are given above, move the sequences past them
+SELECT setval('publishers_id_seq', (SELECT max(id) FROM publishers));
+SELECT setval('contents_id_seq', (SELECT max(id) FROM contents));
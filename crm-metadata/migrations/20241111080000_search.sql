@@ -0,0 +1,47 @@
+-- names of the publishers of a content, kept up to date by the triggers below so that they
+-- could be indexed along with the content
+ALTER TABLE contents ADD COLUMN publisher_names text NOT NULL DEFAULT '';
+
+-- names weigh more than descriptions, which weigh more than publishers when ranking
+ALTER TABLE contents ADD COLUMN search tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', name), 'A')
+    || setweight(to_tsvector('simple', description), 'B')
+    || setweight(to_tsvector('simple', publisher_names), 'C')
+) STORED;
+
+CREATE INDEX contents_search_idx ON contents USING GIN (search);
+
+CREATE FUNCTION refresh_publisher_names(content_ids int[]) RETURNS void AS $$
+    UPDATE contents c SET publisher_names = COALESCE((
+        SELECT string_agg(p.name, ' ' ORDER BY p.id) FROM content_publishers cp
+        JOIN publishers p ON p.id = cp.publisher_id
+        WHERE cp.content_id = c.id
+    ), '')
+    WHERE c.id = ANY(content_ids);
+$$ LANGUAGE sql;
+
+CREATE FUNCTION content_publishers_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM refresh_publisher_names(ARRAY[OLD.content_id]);
+    ELSE
+        PERFORM refresh_publisher_names(ARRAY[NEW.content_id]);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER content_publishers_changed AFTER INSERT OR DELETE ON content_publishers
+FOR EACH ROW EXECUTE FUNCTION content_publishers_changed();
+
+CREATE FUNCTION publisher_renamed() RETURNS trigger AS $$
+BEGIN
+    PERFORM refresh_publisher_names(
+        ARRAY(SELECT content_id FROM content_publishers WHERE publisher_id = NEW.id)
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER publisher_renamed AFTER UPDATE OF name ON publishers
+FOR EACH ROW EXECUTE FUNCTION publisher_renamed();
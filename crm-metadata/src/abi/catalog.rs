@@ -0,0 +1,497 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeZone as _, Utc};
+use prost_types::Timestamp;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Postgres, QueryBuilder, Row, Transaction};
+use tonic::{Code, Response, Status};
+use tracing::warn;
+
+use super::page;
+use crate::pb::{
+    materialize_response, Content, ContentType, CreateContentRequest, DeleteContentRequest,
+    DeleteResponse, ListContentsRequest, ListContentsResponse, MaterializeError,
+    MaterializeResponse, Publisher, UpdateContentRequest,
+};
+use crate::{MetadataService, ServiceResult};
+
+const MAX_NAME_LEN: usize = 256;
+pub(super) const MAX_URL_LEN: usize = 256;
+
+pub(super) const CONTENT_COLUMNS: &str =
+    "id, name, description, url, image, type::text AS type, created_at, views, likes, dislikes";
+
+const SELECT_PUBLISHERS: &str = r#"
+SELECT cp.content_id, p.id, p.name, p.avatar FROM publishers p
+JOIN content_publishers cp ON cp.publisher_id = p.id
+WHERE cp.content_id = ANY($1)
+ORDER BY p.id
+"#;
+
+const INSERT_CONTENT: &str = r#"
+INSERT INTO contents (name, description, url, image, type)
+VALUES ($1, $2, $3, $4, $5::content_type)
+RETURNING id
+"#;
+
+// fields that are NULL are left unchanged
+const UPDATE_CONTENT: &str = r#"
+UPDATE contents SET
+    name = COALESCE($2, name),
+    description = COALESCE($3, description),
+    url = COALESCE($4, url),
+    image = COALESCE($5, image),
+    type = COALESCE($6::content_type, type)
+WHERE id = $1
+"#;
+
+const INSERT_CONTENT_PUBLISHERS: &str = r#"
+INSERT INTO content_publishers (content_id, publisher_id)
+SELECT $1, unnest($2::int[])
+ON CONFLICT DO NOTHING
+"#;
+
+impl MetadataService {
+    /// Look the content up in the catalog along with its publishers.
+    pub(crate) async fn content(&self, id: u32) -> Result<Content, Status> {
+        let sql = format!("SELECT {CONTENT_COLUMNS} FROM contents WHERE id = $1");
+        let content: Option<Content> = sqlx::query_as(&sql)
+            .bind(id as i32)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+        let Some(mut content) = content else {
+            return Err(Status::not_found(format!("Content {id} not found")));
+        };
+
+        content.publishers = self
+            .publishers_of(&[id])
+            .await?
+            .remove(&id)
+            .unwrap_or_default();
+        Ok(content)
+    }
+
+    pub async fn create_content(&self, req: CreateContentRequest) -> ServiceResult<Content> {
+        req.validate()?;
+        let r#type = content_type(req.r#type)?;
+
+        let mut tx = self.pool.begin().await.map_err(catalog_error)?;
+        let id: i32 = sqlx::query_scalar(INSERT_CONTENT)
+            .bind(&req.name)
+            .bind(&req.description)
+            .bind(&req.url)
+            .bind(&req.image)
+            .bind(r#type.as_sql())
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(catalog_error)?;
+        link_publishers(&mut tx, id, &req.publisher_ids).await?;
+        tx.commit().await.map_err(catalog_error)?;
+
+        Ok(Response::new(self.content(id as _).await?))
+    }
+
+    pub async fn update_content(&self, req: UpdateContentRequest) -> ServiceResult<Content> {
+        req.validate()?;
+        let r#type = req.r#type.map(content_type).transpose()?;
+
+        let mut tx = self.pool.begin().await.map_err(catalog_error)?;
+        let ret = sqlx::query(UPDATE_CONTENT)
+            .bind(req.id as i32)
+            .bind(&req.name)
+            .bind(&req.description)
+            .bind(&req.url)
+            .bind(&req.image)
+            .bind(r#type.map(|t| t.as_sql()))
+            .execute(&mut *tx)
+            .await
+            .map_err(catalog_error)?;
+        if ret.rows_affected() == 0 {
+            return Err(Status::not_found(format!("Content {} not found", req.id)));
+        }
+
+        if let Some(publishers) = &req.publishers {
+            sqlx::query("DELETE FROM content_publishers WHERE content_id = $1")
+                .bind(req.id as i32)
+                .execute(&mut *tx)
+                .await
+                .map_err(catalog_error)?;
+            link_publishers(&mut tx, req.id as _, &publishers.ids).await?;
+        }
+        tx.commit().await.map_err(catalog_error)?;
+
+        Ok(Response::new(self.content(req.id).await?))
+    }
+
+    pub async fn delete_content(&self, req: DeleteContentRequest) -> ServiceResult<DeleteResponse> {
+        let ret = sqlx::query("DELETE FROM contents WHERE id = $1")
+            .bind(req.id as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+        if ret.rows_affected() == 0 {
+            return Err(Status::not_found(format!("Content {} not found", req.id)));
+        }
+        Ok(Response::new(DeleteResponse {}))
+    }
+
+    pub async fn list_contents(
+        &self,
+        req: ListContentsRequest,
+    ) -> ServiceResult<ListContentsResponse> {
+        let limit = req.limit();
+        // fetch one more row to know whether there is a next page
+        let mut qb = req.to_builder(req.after()?, limit + 1)?;
+        let mut contents: Vec<Content> = qb
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+
+        let next_cursor = if contents.len() > limit as usize {
+            contents.truncate(limit as _);
+            contents.last().map(|c| page::encode_cursor(c.id))
+        } else {
+            None
+        };
+
+        let ids: Vec<_> = contents.iter().map(|c| c.id).collect();
+        let mut publishers = self.publishers_of(&ids).await?;
+        for content in contents.iter_mut() {
+            content.publishers = publishers.remove(&content.id).unwrap_or_default();
+        }
+
+        Ok(Response::new(ListContentsResponse {
+            contents,
+            next_cursor: next_cursor.unwrap_or_default(),
+        }))
+    }
+
+    /// Publishers of each of the contents, keyed by content id.
+    pub(super) async fn publishers_of(
+        &self,
+        ids: &[u32],
+    ) -> Result<HashMap<u32, Vec<Publisher>>, Status> {
+        let ids: Vec<i32> = ids.iter().map(|id| *id as _).collect();
+        let rows: Vec<PgRow> = sqlx::query(SELECT_PUBLISHERS)
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+
+        let mut publishers: HashMap<u32, Vec<Publisher>> = HashMap::new();
+        for row in rows {
+            let content_id = row.try_get::<i32, _>("content_id").map_err(catalog_error)?;
+            let publisher = Publisher::from_row(&row).map_err(catalog_error)?;
+            publishers
+                .entry(content_id as _)
+                .or_default()
+                .push(publisher);
+        }
+        Ok(publishers)
+    }
+}
+
+impl CreateContentRequest {
+    fn validate(&self) -> Result<(), Status> {
+        check_name(&self.name, MAX_NAME_LEN)?;
+        check_url("url", &self.url)?;
+        check_len("image", &self.image, MAX_URL_LEN)
+    }
+}
+
+impl UpdateContentRequest {
+    fn validate(&self) -> Result<(), Status> {
+        if let Some(name) = &self.name {
+            check_name(name, MAX_NAME_LEN)?;
+        }
+        if let Some(url) = &self.url {
+            check_url("url", url)?;
+        }
+        if let Some(image) = &self.image {
+            check_len("image", image, MAX_URL_LEN)?;
+        }
+        Ok(())
+    }
+}
+
+impl ListContentsRequest {
+    fn to_builder(
+        &self,
+        after: Option<i32>,
+        limit: u32,
+    ) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let mut qb =
+            QueryBuilder::new(format!("SELECT {CONTENT_COLUMNS} FROM contents WHERE true"));
+        match content_type(self.r#type)? {
+            ContentType::Unspecified => {}
+            t => {
+                qb.push(" AND type = ")
+                    .push_bind(t.as_sql())
+                    .push("::content_type");
+            }
+        }
+        if self.publisher_id != 0 {
+            qb.push(
+                " AND EXISTS (SELECT 1 FROM content_publishers cp \
+                 WHERE cp.content_id = contents.id AND cp.publisher_id = ",
+            )
+            .push_bind(self.publisher_id as i32)
+            .push(")");
+        }
+        if let Some(ts) = &self.created_after {
+            qb.push(" AND created_at >= ").push_bind(ts_to_utc(ts)?);
+        }
+        if let Some(ts) = &self.created_before {
+            qb.push(" AND created_at < ").push_bind(ts_to_utc(ts)?);
+        }
+        if let Some(id) = after {
+            qb.push(" AND id > ").push_bind(id);
+        }
+        qb.push(" ORDER BY id LIMIT ").push_bind(limit as i64);
+        Ok(qb)
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for Content {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let created_at: DateTime<Utc> = row.try_get("created_at")?;
+        let r#type: String = row.try_get("type")?;
+        Ok(Content {
+            id: row.try_get::<i32, _>("id")? as _,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            // publishers are kept in a table of their own
+            publishers: vec![],
+            url: row.try_get("url")?,
+            image: row.try_get("image")?,
+            r#type: ContentType::from_sql(&r#type) as _,
+            created_at: Some(Timestamp {
+                seconds: created_at.timestamp(),
+                nanos: created_at.timestamp_subsec_nanos() as _,
+            }),
+            views: row.try_get::<i64, _>("views")? as _,
+            likes: row.try_get::<i64, _>("likes")? as _,
+            dislikes: row.try_get::<i64, _>("dislikes")? as _,
+        })
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for Publisher {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Publisher {
+            id: row.try_get::<i32, _>("id")? as _,
+            name: row.try_get("name")?,
+            avatar: row.try_get("avatar")?,
+        })
+    }
+}
+
+impl ContentType {
+    /// value of the postgres `content_type` enum
+    pub(crate) fn as_sql(&self) -> &'static str {
+        match self {
+            ContentType::Unspecified => "unspecified",
+            ContentType::Short => "short",
+            ContentType::Vlog => "vlog",
+            ContentType::Movie => "movie",
+            ContentType::AiGenerated => "ai_generated",
+        }
+    }
+
+    pub(crate) fn from_sql(value: &str) -> Self {
+        match value {
+            "short" => ContentType::Short,
+            "vlog" => ContentType::Vlog,
+            "movie" => ContentType::Movie,
+            "ai_generated" => ContentType::AiGenerated,
+            _ => ContentType::Unspecified,
+        }
+    }
+}
+
+impl MaterializeResponse {
+    pub fn new(id: u32, ret: Result<Content, Status>) -> Self {
+        let result = match ret {
+            Ok(content) => materialize_response::Result::Content(content),
+            Err(e) => materialize_response::Result::Error(MaterializeError {
+                id,
+                code: e.code() as _,
+                message: e.message().to_string(),
+            }),
+        };
+        Self {
+            result: Some(result),
+        }
+    }
+
+    /// The content, or why the content could not be materialized.
+    pub fn into_result(self) -> Result<Content, Status> {
+        match self.result {
+            Some(materialize_response::Result::Content(content)) => Ok(content),
+            Some(materialize_response::Result::Error(e)) => {
+                Err(Status::new(Code::from_i32(e.code), e.message))
+            }
+            None => Err(Status::internal("Empty materialize response")),
+        }
+    }
+}
+
+async fn link_publishers(
+    tx: &mut Transaction<'_, Postgres>,
+    content_id: i32,
+    publisher_ids: &[u32],
+) -> Result<(), Status> {
+    let ids: Vec<i32> = publisher_ids.iter().map(|id| *id as _).collect();
+    sqlx::query(INSERT_CONTENT_PUBLISHERS)
+        .bind(content_id)
+        .bind(ids)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(e) if e.is_foreign_key_violation() => {
+                Status::invalid_argument(format!("Unknown publisher in {publisher_ids:?}"))
+            }
+            _ => catalog_error(e),
+        })?;
+    Ok(())
+}
+
+pub(super) fn content_type(value: i32) -> Result<ContentType, Status> {
+    ContentType::try_from(value)
+        .map_err(|_| Status::invalid_argument(format!("Invalid content type: {value}")))
+}
+
+pub(super) fn check_name(name: &str, max: usize) -> Result<(), Status> {
+    if name.trim().is_empty() {
+        return Err(Status::invalid_argument("Name should not be empty"));
+    }
+    check_len("name", name, max)
+}
+
+fn check_url(field: &str, url: &str) -> Result<(), Status> {
+    if url.trim().is_empty() {
+        return Err(Status::invalid_argument(format!(
+            "{field} should not be empty"
+        )));
+    }
+    check_len(field, url, MAX_URL_LEN)
+}
+
+pub(super) fn check_len(field: &str, value: &str, max: usize) -> Result<(), Status> {
+    if value.chars().count() > max {
+        return Err(Status::invalid_argument(format!(
+            "{field} should be at most {max} characters"
+        )));
+    }
+    Ok(())
+}
+
+pub(super) fn ts_to_utc(ts: &Timestamp) -> Result<DateTime<Utc>, Status> {
+    Utc.timestamp_opt(ts.seconds, ts.nanos as _)
+        .single()
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid timestamp: {ts}")))
+}
+
+pub(super) fn catalog_error(e: sqlx::Error) -> Status {
+    warn!("Failed to access content catalog: {:?}", e);
+    Status::internal("Failed to access content catalog")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn contents_should_be_listed_by_page() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut req = ListContentsRequest {
+            page_size: 2,
+            ..Default::default()
+        };
+
+        let mut ids = vec![];
+        loop {
+            let ret = service.list_contents(req.clone()).await?.into_inner();
+            ids.extend(ret.contents.iter().map(|c| c.id));
+            if ret.next_cursor.is_empty() {
+                break;
+            }
+            req.cursor = ret.next_cursor;
+        }
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn contents_should_be_filtered() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let list = |req: ListContentsRequest| {
+            let service = service.clone();
+            async move {
+                let ret = service.list_contents(req).await?.into_inner();
+                Ok::<_, Status>(ret.contents.iter().map(|c| c.id).collect::<Vec<_>>())
+            }
+        };
+
+        let movies = list(ListContentsRequest {
+            r#type: ContentType::Movie as _,
+            ..Default::default()
+        });
+        assert_eq!(movies.await?, [1, 4]);
+
+        let acme = list(ListContentsRequest {
+            publisher_id: 1,
+            ..Default::default()
+        });
+        assert_eq!(acme.await?, [1, 3, 5]);
+
+        let summer = list(ListContentsRequest {
+            created_after: Some(Timestamp {
+                seconds: Utc
+                    .with_ymd_and_hms(2024, 6, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp(),
+                nanos: 0,
+            }),
+            created_before: Some(Timestamp {
+                seconds: Utc
+                    .with_ymd_and_hms(2024, 9, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp(),
+                nanos: 0,
+            }),
+            ..Default::default()
+        });
+        assert_eq!(summer.await?, [2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_content_should_not_be_updated() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let req = UpdateContentRequest {
+            id: 999,
+            name: Some("Nothing".to_string()),
+            ..Default::default()
+        };
+        let status = service.update_content(req).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let req = UpdateContentRequest {
+            id: 1,
+            name: Some(" ".to_string()),
+            ..Default::default()
+        };
+        let status = service.update_content(req).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+}
@@ -1,35 +1,45 @@
+#![allow(clippy::result_large_err)]
+
 use std::collections::HashSet;
 
-use chrono::{DateTime, Days, Utc};
-use fake::{
-    faker::{chrono::en::DateTimeBetween, lorem::en::Sentence, name::raw::Name},
-    locales::EN,
-    Fake, Faker,
-};
 use futures::{stream, Stream, StreamExt as _};
-use prost_types::Timestamp;
-use rand::Rng as _;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
+use tracing::warn;
 
 use crate::{
-    pb::{Content, MaterializeRequest, Publisher},
+    pb::{MaterializeRequest, MaterializeResponse},
     MetadataService, ResponseStream, ServiceResult,
 };
 
+mod catalog;
+mod page;
+mod publisher;
+mod recommend;
+mod search;
+mod tpl;
+
+pub use tpl::{Format, Recipient, TemplateSource, Templates, Tpl, DEFAULT_TEMPLATE};
+
 const CHANNEL_SIZE: usize = 1024;
 
 impl MetadataService {
+    /// Look each requested content up in the catalog, unknown contents get a NOT_FOUND error of
+    /// their own instead of failing the whole stream.
     pub async fn materialize(
         &self,
         mut stream: impl Stream<Item = Result<MaterializeRequest, Status>> + Send + 'static + Unpin,
     ) -> ServiceResult<ResponseStream> {
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let svc = self.clone();
         tokio::spawn(async move {
             while let Some(Ok(req)) = stream.next().await {
-                let content = Content::materialize(req.id);
-                tx.send(Ok(content)).await.unwrap();
+                let resp = MaterializeResponse::new(req.id, svc.content(req.id).await);
+                if tx.send(Ok(resp)).await.is_err() {
+                    warn!("Receiver dropped, stop materializing contents");
+                    break;
+                }
             }
         });
 
@@ -39,39 +49,6 @@ impl MetadataService {
     }
 }
 
-impl Content {
-    pub fn materialize(id: u32) -> Self {
-        let mut rng = rand::thread_rng();
-        Content {
-            id,
-            name: Name(EN).fake(),
-            description: Sentence(3..7).fake(),
-            publishers: (1..rng.gen_range(2..10))
-                .map(|_| Publisher::new())
-                .collect(),
-            url: "https://placehold.com/1600x900".to_string(),
-            image: "https://placehold.com/1600x900".to_string(),
-            r#type: Faker.fake(),
-            created_at: created_at(),
-            views: rng.gen_range(123432..10000000),
-            likes: rng.gen_range(1234..100000),
-            dislikes: rng.gen_range(123..10000),
-        }
-    }
-
-    pub fn to_body(&self) -> String {
-        format!("Content: {:?}", self)
-    }
-}
-
-pub struct Tpl<'a>(pub &'a [Content]);
-
-impl<'a> Tpl<'a> {
-    pub fn to_body(&self) -> String {
-        format!("Tpl: {:?}", self.0)
-    }
-}
-
 impl MaterializeRequest {
     pub fn new_with_ids(ids: &[u32]) -> impl Stream<Item = Self> {
         let reqs: HashSet<_> = ids.iter().map(|id| Self { id: *id }).collect();
@@ -79,50 +56,35 @@ impl MaterializeRequest {
     }
 }
 
-impl Publisher {
-    pub fn new() -> Self {
-        Publisher {
-            id: (10000..2000000).fake(),
-            name: Name(EN).fake(),
-            avatar: "https://placehold.co/400x400".to_string(),
-        }
-    }
-}
-
-fn before(days: u64) -> DateTime<Utc> {
-    Utc::now().checked_sub_days(Days::new(days)).unwrap()
-}
-
-fn created_at() -> Option<Timestamp> {
-    let date: DateTime<Utc> = DateTimeBetween(before(365), before(0)).fake();
-    Some(Timestamp {
-        seconds: date.timestamp(),
-        nanos: date.timestamp_subsec_nanos() as i32,
-    })
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::AppConfig;
-
     use super::*;
     use anyhow::Result;
+    use tonic::Code;
 
     #[tokio::test]
     async fn test_materialize_should_work() -> Result<()> {
-        let config = AppConfig::try_load()?;
-
-        let service = MetadataService::new(config).await;
+        let (_tdb, service) = MetadataService::new_for_test().await?;
         let stream = tokio_stream::iter(vec![
             Ok(MaterializeRequest { id: 1 }),
-            Ok(MaterializeRequest { id: 2 }),
+            Ok(MaterializeRequest { id: 999 }),
             Ok(MaterializeRequest { id: 3 }),
         ]);
 
         let response = service.materialize(stream).await?;
-        let ret = response.into_inner().collect::<Vec<_>>().await;
+        let ret = response
+            .into_inner()
+            .map(|v| v.unwrap().into_result())
+            .collect::<Vec<_>>()
+            .await;
         assert_eq!(ret.len(), 3);
 
+        let content = ret[0].as_ref().unwrap();
+        assert_eq!(content.id, 1);
+        assert!(!content.publishers.is_empty());
+        assert_eq!(ret[1].as_ref().unwrap_err().code(), Code::NotFound);
+        assert_eq!(ret[2].as_ref().unwrap().id, 3);
+
         Ok(())
     }
 }
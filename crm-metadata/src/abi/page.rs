@@ -0,0 +1,89 @@
+#![allow(clippy::result_large_err)]
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use tonic::Status;
+
+use crate::pb::{ListContentsRequest, ListPublishersRequest};
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+impl ListContentsRequest {
+    pub(super) fn limit(&self) -> u32 {
+        limit(self.page_size)
+    }
+
+    /// id of the last content of the previous page, if any
+    pub(super) fn after(&self) -> Result<Option<i32>, Status> {
+        after(&self.cursor)
+    }
+}
+
+impl ListPublishersRequest {
+    pub(super) fn limit(&self) -> u32 {
+        limit(self.page_size)
+    }
+
+    /// id of the last publisher of the previous page, if any
+    pub(super) fn after(&self) -> Result<Option<i32>, Status> {
+        after(&self.cursor)
+    }
+}
+
+/// The cursor is opaque to clients, it is the id of the last record returned.
+pub(super) fn encode_cursor(id: u32) -> String {
+    URL_SAFE_NO_PAD.encode(id.to_string())
+}
+
+/// page size with the default applied and capped at the max
+fn limit(page_size: u32) -> u32 {
+    match page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        n => n.min(MAX_PAGE_SIZE),
+    }
+}
+
+fn after(cursor: &str) -> Result<Option<i32>, Status> {
+    if cursor.is_empty() {
+        return Ok(None);
+    }
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|v| String::from_utf8(v).ok())
+        .and_then(|v| v.parse().ok())
+        .map(Some)
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid cursor: {cursor}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn cursor_should_round_trip() {
+        let req = ListContentsRequest {
+            cursor: encode_cursor(42),
+            ..Default::default()
+        };
+        assert_eq!(req.after().unwrap(), Some(42));
+        assert_eq!(ListContentsRequest::default().after().unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_cursor_should_be_rejected() {
+        let req = ListPublishersRequest {
+            cursor: URL_SAFE_NO_PAD.encode("not a number"),
+            ..Default::default()
+        };
+        assert_eq!(req.after().unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn page_size_should_be_defaulted_and_capped() {
+        assert_eq!(limit(0), DEFAULT_PAGE_SIZE);
+        assert_eq!(limit(10), 10);
+        assert_eq!(limit(100000), MAX_PAGE_SIZE);
+    }
+}
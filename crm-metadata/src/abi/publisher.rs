@@ -0,0 +1,109 @@
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use super::catalog::{catalog_error, check_len, check_name, MAX_URL_LEN};
+use super::page;
+use crate::pb::{
+    CreatePublisherRequest, DeletePublisherRequest, DeleteResponse, ListPublishersRequest,
+    ListPublishersResponse, Publisher, UpdatePublisherRequest,
+};
+use crate::{MetadataService, ServiceResult};
+
+const MAX_NAME_LEN: usize = 64;
+
+const INSERT_PUBLISHER: &str = r#"
+INSERT INTO publishers (name, avatar) VALUES ($1, $2)
+RETURNING id, name, avatar
+"#;
+
+// fields that are NULL are left unchanged
+const UPDATE_PUBLISHER: &str = r#"
+UPDATE publishers SET
+    name = COALESCE($2, name),
+    avatar = COALESCE($3, avatar)
+WHERE id = $1
+RETURNING id, name, avatar
+"#;
+
+impl MetadataService {
+    pub async fn create_publisher(&self, req: CreatePublisherRequest) -> ServiceResult<Publisher> {
+        check_name(&req.name, MAX_NAME_LEN)?;
+        check_len("avatar", &req.avatar, MAX_URL_LEN)?;
+
+        let publisher = sqlx::query_as(INSERT_PUBLISHER)
+            .bind(&req.name)
+            .bind(&req.avatar)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+        Ok(Response::new(publisher))
+    }
+
+    pub async fn update_publisher(&self, req: UpdatePublisherRequest) -> ServiceResult<Publisher> {
+        if let Some(name) = &req.name {
+            check_name(name, MAX_NAME_LEN)?;
+        }
+        if let Some(avatar) = &req.avatar {
+            check_len("avatar", avatar, MAX_URL_LEN)?;
+        }
+
+        let publisher: Option<Publisher> = sqlx::query_as(UPDATE_PUBLISHER)
+            .bind(req.id as i32)
+            .bind(&req.name)
+            .bind(&req.avatar)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+        publisher
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("Publisher {} not found", req.id)))
+    }
+
+    /// The publisher is removed from its contents as well, the contents are kept.
+    pub async fn delete_publisher(
+        &self,
+        req: DeletePublisherRequest,
+    ) -> ServiceResult<DeleteResponse> {
+        let ret = sqlx::query("DELETE FROM publishers WHERE id = $1")
+            .bind(req.id as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+        if ret.rows_affected() == 0 {
+            return Err(Status::not_found(format!("Publisher {} not found", req.id)));
+        }
+        Ok(Response::new(DeleteResponse {}))
+    }
+
+    pub async fn list_publishers(
+        &self,
+        req: ListPublishersRequest,
+    ) -> ServiceResult<ListPublishersResponse> {
+        let limit = req.limit();
+        let mut qb: QueryBuilder<Postgres> =
+            QueryBuilder::new("SELECT id, name, avatar FROM publishers");
+        if let Some(id) = req.after()? {
+            qb.push(" WHERE id > ").push_bind(id);
+        }
+        // fetch one more row to know whether there is a next page
+        qb.push(" ORDER BY id LIMIT ").push_bind(limit as i64 + 1);
+
+        let mut publishers: Vec<Publisher> = qb
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+
+        let next_cursor = if publishers.len() > limit as usize {
+            publishers.truncate(limit as _);
+            publishers.last().map(|p| page::encode_cursor(p.id))
+        } else {
+            None
+        };
+
+        Ok(Response::new(ListPublishersResponse {
+            publishers,
+            next_cursor: next_cursor.unwrap_or_default(),
+        }))
+    }
+}
@@ -0,0 +1,136 @@
+#![allow(clippy::result_large_err)]
+
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use super::catalog::{catalog_error, content_type, ts_to_utc, CONTENT_COLUMNS};
+use crate::pb::{Content, RecommendRequest, RecommendResponse};
+use crate::{MetadataService, ServiceResult};
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+// views count the most, but a content that is widely disliked is pushed down. likes and dislikes
+// are smoothed so that a content with few votes is neither loved nor hated.
+const POPULARITY: &str = "ln(1 + views) * (likes + 1)::float8 / (likes + dislikes + 2)";
+
+impl MetadataService {
+    pub async fn recommend(&self, req: RecommendRequest) -> ServiceResult<RecommendResponse> {
+        let mut qb = req.to_builder()?;
+        let mut contents: Vec<Content> = qb
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+
+        let ids: Vec<_> = contents.iter().map(|c| c.id).collect();
+        let mut publishers = self.publishers_of(&ids).await?;
+        for content in contents.iter_mut() {
+            content.publishers = publishers.remove(&content.id).unwrap_or_default();
+        }
+
+        Ok(Response::new(RecommendResponse { contents }))
+    }
+}
+
+impl RecommendRequest {
+    /// number of contents with the default applied and capped at the max
+    fn limit(&self) -> u32 {
+        match self.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        }
+    }
+
+    fn to_builder(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let watched: Vec<i32> = self
+            .recent_watched
+            .iter()
+            .chain(&self.finished)
+            .map(|id| *id as _)
+            .collect();
+        let types = self
+            .types
+            .iter()
+            .map(|t| content_type(*t).map(|t| t.as_sql()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT {CONTENT_COLUMNS} FROM contents WHERE NOT (id = ANY("
+        ));
+        qb.push_bind(watched).push("))");
+        if !types.is_empty() {
+            qb.push(" AND type = ANY(")
+                .push_bind(types)
+                .push("::content_type[])");
+        }
+        if let Some(ts) = &self.created_after {
+            qb.push(" AND created_at >= ").push_bind(ts_to_utc(ts)?);
+        }
+        qb.push(format!(" ORDER BY {POPULARITY} DESC, id LIMIT "))
+            .push_bind(self.limit() as i64);
+        Ok(qb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::ContentType;
+    use anyhow::Result;
+    use chrono::{TimeZone as _, Utc};
+    use prost_types::Timestamp;
+
+    async fn recommend(req: RecommendRequest) -> Result<Vec<u32>> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let ret = service.recommend(req).await?.into_inner();
+        Ok(ret.contents.iter().map(|c| c.id).collect())
+    }
+
+    #[tokio::test]
+    async fn contents_should_be_ranked_by_popularity() -> Result<()> {
+        let ids = recommend(RecommendRequest::default()).await?;
+        assert_eq!(ids, [4, 1, 2, 3, 5]);
+
+        let ids = recommend(RecommendRequest {
+            limit: 2,
+            ..Default::default()
+        })
+        .await?;
+        assert_eq!(ids, [4, 1]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watched_contents_should_not_be_recommended() -> Result<()> {
+        let ids = recommend(RecommendRequest {
+            recent_watched: vec![4],
+            finished: vec![1, 3],
+            ..Default::default()
+        })
+        .await?;
+        assert_eq!(ids, [2, 5]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn contents_should_be_filtered_by_type_and_recency() -> Result<()> {
+        let ids = recommend(RecommendRequest {
+            types: vec![ContentType::Movie as _, ContentType::AiGenerated as _],
+            created_after: Some(Timestamp {
+                seconds: Utc
+                    .with_ymd_and_hms(2024, 6, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp(),
+                nanos: 0,
+            }),
+            ..Default::default()
+        })
+        .await?;
+        assert_eq!(ids, [4, 5]);
+
+        Ok(())
+    }
+}
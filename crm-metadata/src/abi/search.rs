@@ -0,0 +1,181 @@
+#![allow(clippy::result_large_err)]
+
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use super::catalog::{catalog_error, content_type, CONTENT_COLUMNS};
+use crate::pb::{Content, SearchOrder, SearchRequest, SearchResponse};
+use crate::{MetadataService, ServiceResult};
+
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+const MAX_QUERY_LEN: usize = 256;
+
+impl MetadataService {
+    pub async fn search(&self, req: SearchRequest) -> ServiceResult<SearchResponse> {
+        let mut qb = req.to_builder()?;
+        let mut contents: Vec<Content> = qb
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(catalog_error)?;
+
+        let ids: Vec<_> = contents.iter().map(|c| c.id).collect();
+        let mut publishers = self.publishers_of(&ids).await?;
+        for content in contents.iter_mut() {
+            content.publishers = publishers.remove(&content.id).unwrap_or_default();
+        }
+
+        Ok(Response::new(SearchResponse { contents }))
+    }
+}
+
+impl SearchRequest {
+    /// number of contents with the default applied and capped at the max
+    fn limit(&self) -> u32 {
+        match self.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        }
+    }
+
+    fn to_builder(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let query = self.query.trim();
+        if query.is_empty() {
+            return Err(Status::invalid_argument("Query should not be empty"));
+        }
+        if query.chars().count() > MAX_QUERY_LEN {
+            return Err(Status::invalid_argument(format!(
+                "Query should be at most {MAX_QUERY_LEN} characters"
+            )));
+        }
+        let order = SearchOrder::try_from(self.order)
+            .map_err(|_| Status::invalid_argument(format!("Invalid order: {}", self.order)))?;
+        let types = self
+            .types
+            .iter()
+            .map(|t| content_type(*t).map(|t| t.as_sql()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT {CONTENT_COLUMNS} FROM contents, websearch_to_tsquery('simple', "
+        ));
+        qb.push_bind(query.to_string())
+            .push(") q WHERE search @@ q");
+        if !types.is_empty() {
+            qb.push(" AND type = ANY(")
+                .push_bind(types)
+                .push("::content_type[])");
+        }
+        let order = match order {
+            SearchOrder::Relevance => "ts_rank(search, q) DESC, views DESC",
+            SearchOrder::Views => "views DESC",
+            SearchOrder::Recency => "created_at DESC",
+        };
+        qb.push(format!(" ORDER BY {order}, id LIMIT "))
+            .push_bind(self.limit() as i64);
+        Ok(qb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::{ContentType, DeletePublisherRequest, UpdatePublisherRequest};
+    use anyhow::Result;
+    use tonic::Code;
+
+    async fn search(
+        service: &MetadataService,
+        query: &str,
+        order: SearchOrder,
+        types: &[ContentType],
+    ) -> Result<Vec<u32>> {
+        let req = SearchRequest {
+            query: query.to_string(),
+            types: types.iter().map(|t| *t as _).collect(),
+            order: order as _,
+            ..Default::default()
+        };
+        let ret = service.search(req).await?.into_inner();
+        Ok(ret.contents.iter().map(|c| c.id).collect())
+    }
+
+    #[tokio::test]
+    async fn names_should_rank_above_descriptions() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let ids = search(&service, "long road", SearchOrder::Relevance, &[]).await?;
+        assert_eq!(ids, [1, 3]);
+
+        let ids = search(&service, "功夫之路", SearchOrder::Relevance, &[]).await?;
+        assert_eq!(ids, [4]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn contents_should_be_found_by_publisher() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let ids = search(&service, "acme", SearchOrder::Views, &[]).await?;
+        assert_eq!(ids, [1, 3, 5]);
+
+        let ids = search(&service, "acme", SearchOrder::Recency, &[]).await?;
+        assert_eq!(ids, [5, 3, 1]);
+
+        let ids = search(&service, "acme", SearchOrder::Views, &[ContentType::Movie]).await?;
+        assert_eq!(ids, [1]);
+
+        let ids = search(&service, "acme -road", SearchOrder::Views, &[]).await?;
+        assert_eq!(ids, [5]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn publisher_changes_should_be_searchable() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let search = |query: &str| {
+            let req = SearchRequest {
+                query: query.to_string(),
+                order: SearchOrder::Views as _,
+                ..Default::default()
+            };
+            let service = service.clone();
+            async move {
+                let ret = service.search(req).await?.into_inner();
+                Ok::<_, Status>(ret.contents.iter().map(|c| c.id).collect::<Vec<_>>())
+            }
+        };
+
+        let req = UpdatePublisherRequest {
+            id: 2,
+            name: Some("Jane Roe".to_string()),
+            ..Default::default()
+        };
+        service.update_publisher(req).await?;
+        assert_eq!(search("roe").await?, [1, 2]);
+        assert!(search("doe").await?.is_empty());
+
+        service
+            .delete_publisher(DeletePublisherRequest { id: 2 })
+            .await?;
+        assert!(search("roe").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn empty_query_should_be_rejected() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let status = service
+            .search(SearchRequest {
+                query: "  ".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,801 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use minijinja::{context, Environment, Error, ErrorKind, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+use tracing::warn;
+
+use crate::pb::{Content, ContentType, Publisher};
+
+/// sms longer than this are truncated
+const MAX_SMS_LEN: usize = 160;
+
+/// name of the builtin templates, used when a message names no template
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+/// the last resort of every locale fallback chain, before templates without a locale
+const DEFAULT_LOCALE: &str = "en";
+
+const DEFAULT_HTML: &str = r#"<p>Hi {{ user.name | default("there") }},</p>
+<ul>
+{%- for content in contents %}
+  <li>
+    <a href="{{ content.url }}"><img src="{{ content.image }}" alt="{{ content.name }}"></a>
+    <a href="{{ content.url }}">{{ content.name }}</a>
+    <small>{{ content.views | number }} views, since {{ content.created_at | date }}</small>
+  </li>
+{%- endfor %}
+</ul>
+"#;
+
+const DEFAULT_TEXT: &str = r#"Hi {{ user.name | default("there") }},
+{% for content in contents %}
+{% include "content.txt" %}
+{%- endfor %}
+"#;
+
+const DEFAULT_SMS: &str = r#"
+{%- if contents -%}
+{{ contents[0].name }}{% if contents | length > 1 %} and {{ contents | length - 1 }} more{% endif %}: {{ contents[0].url }}
+{%- endif -%}
+"#;
+
+const CONTENT_TEXT: &str =
+    r#"- {{ content.name }} ({{ content.views | compact }} views): {{ content.url }}"#;
+
+const DEFAULT_HTML_ZH: &str = r#"<p>{% if user.name %}{{ user.name }}，{% endif %}你好：</p>
+<ul>
+{%- for content in contents %}
+  <li>
+    <a href="{{ content.url }}"><img src="{{ content.image }}" alt="{{ content.name }}"></a>
+    <a href="{{ content.url }}">{{ content.name }}</a>
+    <small>{{ content.views | number }} 次观看，发布于 {{ content.created_at | date }}</small>
+  </li>
+{%- endfor %}
+</ul>
+"#;
+
+const DEFAULT_TEXT_ZH: &str = r#"{% if user.name %}{{ user.name }}，{% endif %}你好：
+{% for content in contents %}
+- {{ content.name }}（{{ content.views | compact }} 次观看）：{{ content.url }}
+{%- endfor %}
+"#;
+
+const DEFAULT_SMS_ZH: &str = r#"
+{%- if contents -%}
+{{ contents[0].name }}{% if contents | length > 1 %}等 {{ contents | length }} 部{% endif %}：{{ contents[0].url }}
+{%- endif -%}
+"#;
+
+const DEFAULT_HTML_ZH_HANT: &str = r#"<p>{% if user.name %}{{ user.name }}，{% endif %}您好：</p>
+<ul>
+{%- for content in contents %}
+  <li>
+    <a href="{{ content.url }}"><img src="{{ content.image }}" alt="{{ content.name }}"></a>
+    <a href="{{ content.url }}">{{ content.name }}</a>
+    <small>{{ content.views | number }} 次觀看，發佈於 {{ content.created_at | date }}</small>
+  </li>
+{%- endfor %}
+</ul>
+"#;
+
+const DEFAULT_TEXT_ZH_HANT: &str = r#"{% if user.name %}{{ user.name }}，{% endif %}您好：
+{% for content in contents %}
+- {{ content.name }}（{{ content.views | compact }} 次觀看）：{{ content.url }}
+{%- endfor %}
+"#;
+
+const DEFAULT_SMS_ZH_HANT: &str = r#"
+{%- if contents -%}
+{{ contents[0].name }}{% if contents | length > 1 %}等 {{ contents | length }} 部{% endif %}：{{ contents[0].url }}
+{%- endif -%}
+"#;
+
+/// the builtin templates in other languages than the default locale: html, text and sms
+const DEFAULT_LOCALES: [(&str, [&str; 3]); 2] = [
+    ("zh", [DEFAULT_HTML_ZH, DEFAULT_TEXT_ZH, DEFAULT_SMS_ZH]),
+    (
+        "zh-Hant",
+        [
+            DEFAULT_HTML_ZH_HANT,
+            DEFAULT_TEXT_ZH_HANT,
+            DEFAULT_SMS_ZH_HANT,
+        ],
+    ),
+];
+
+/// translations of the subjects campaigns fall back to, keyed by the subject in the default
+/// locale
+const DEFAULT_SUBJECTS: [(&str, [(&str, &str); 2]); 3] = [
+    ("Welcome", [("zh", "欢迎"), ("zh-Hant", "歡迎")]),
+    (
+        "We miss you",
+        [("zh", "我们想你了"), ("zh-Hant", "我們想你了")],
+    ),
+    (
+        "Continue watching",
+        [("zh", "继续观看"), ("zh-Hant", "繼續觀看")],
+    ),
+];
+
+static BUILTIN: LazyLock<Templates> = LazyLock::new(Templates::default);
+
+/// Forms a message is rendered in, each kept in a file of its own extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// subject of emails and title of in-app messages, the subject of the campaign if the
+    /// template has none
+    Subject,
+    /// html body of emails
+    Html,
+    /// plain text body of emails
+    Text,
+    /// short form of sms and in-app messages
+    Sms,
+}
+
+/// Sources of a named template given in config, formats not given fall back to the builtin
+/// templates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateSource {
+    pub subject: Option<String>,
+    pub html: Option<String>,
+    pub text: Option<String>,
+    pub sms: Option<String>,
+    /// the template in other languages keyed by language tag, locales of their own are ignored
+    #[serde(default)]
+    pub locales: HashMap<String, TemplateSource>,
+}
+
+/// What templates know about the recipient, available as `user`. Fields which are unknown are
+/// left out, so that `{{ user.name | default("there") }}` falls back, and lookups through them
+/// render as empty instead of failing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Recipient {
+    pub email: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// female, male or unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    /// content ids of the watch history, the most recent first
+    pub recent_watched: Vec<u32>,
+    pub viewed_but_not_started: Vec<u32>,
+    pub started_but_not_finished: Vec<u32>,
+    pub finished: Vec<u32>,
+    /// language tag such as zh-TW
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+}
+
+/// Named templates of messages. A template `<name>` consists of `<name>.subject`,
+/// `<name>.html`, `<name>.txt` and `<name>.sms`, along with their translations such as
+/// `<name>.zh-TW.txt`. `content.txt` is the partial to render a single content.
+#[derive(Clone)]
+pub struct Templates {
+    env: Environment<'static>,
+    names: HashSet<String>,
+}
+
+/// A message body to render for a recipient.
+pub struct Tpl<'a> {
+    templates: &'a Templates,
+    name: &'a str,
+    recipient: &'a Recipient,
+    contents: &'a [Content],
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Subject => "subject",
+            Format::Html => "html",
+            Format::Text => "txt",
+            Format::Sms => "sms",
+        }
+    }
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "subject" => Some(Format::Subject),
+            "html" => Some(Format::Html),
+            "txt" => Some(Format::Text),
+            "sms" => Some(Format::Sms),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Chainable);
+        env.add_filter("date", date);
+        env.add_filter("number", number);
+        env.add_filter("compact", compact);
+
+        let mut templates = Self {
+            env,
+            names: HashSet::new(),
+        };
+        for (format, source) in [
+            (Format::Html, DEFAULT_HTML),
+            (Format::Text, DEFAULT_TEXT),
+            (Format::Sms, DEFAULT_SMS),
+        ] {
+            templates
+                .add(DEFAULT_TEMPLATE, None, format, source.to_string())
+                .expect("builtin templates should compile");
+        }
+        for (locale, sources) in DEFAULT_LOCALES {
+            for (format, source) in [Format::Html, Format::Text, Format::Sms]
+                .into_iter()
+                .zip(sources)
+            {
+                templates
+                    .add(DEFAULT_TEMPLATE, Some(locale), format, source.to_string())
+                    .expect("builtin templates should compile");
+            }
+        }
+        templates
+            .env
+            .add_template("content.txt", CONTENT_TEXT)
+            .expect("builtin templates should compile");
+        templates
+    }
+}
+
+impl Templates {
+    /// Add a template of the format in the locale, or without a locale, replacing the one of
+    /// the same name.
+    pub fn add(
+        &mut self,
+        name: &str,
+        locale: Option<&str>,
+        format: Format,
+        source: String,
+    ) -> Result<()> {
+        let file = file_name(name, locale, format);
+        if let Err(e) = self.env.add_template_owned(file.clone(), source) {
+            bail!("Invalid template {file}: {e}");
+        }
+        self.names.insert(name.to_string());
+        Ok(())
+    }
+
+    pub fn add_source(&mut self, name: &str, source: &TemplateSource) -> Result<()> {
+        self.add_localized(name, None, source)?;
+        for (locale, source) in &source.locales {
+            self.add_localized(name, Some(&normalize(locale)), source)?;
+        }
+        self.names.insert(name.to_string());
+        Ok(())
+    }
+
+    fn add_localized(
+        &mut self,
+        name: &str,
+        locale: Option<&str>,
+        source: &TemplateSource,
+    ) -> Result<()> {
+        for (format, source) in [
+            (Format::Subject, &source.subject),
+            (Format::Html, &source.html),
+            (Format::Text, &source.text),
+            (Format::Sms, &source.sms),
+        ] {
+            if let Some(source) = source {
+                self.add(name, locale, format, source.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the templates of a directory, named `<name>.<ext>` or `<name>.<locale>.<ext>`. Files
+    /// of other extensions are ignored.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let (Some(stem), Some(format)) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension()
+                    .and_then(|s| s.to_str())
+                    .and_then(Format::from_extension),
+            ) else {
+                continue;
+            };
+            let (name, locale) = match stem.split_once('.') {
+                Some((name, locale)) => (name, Some(normalize(locale))),
+                None => (stem, None),
+            };
+            self.add(name, locale.as_deref(), format, fs::read_to_string(&path)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// Render the template in the format for the recipient, in the first language of the
+    /// locale chain of the recipient the template has, say zh-TW, zh, en, then without a
+    /// locale. Falls back to the builtin template if the named one has no such format, and
+    /// returns None if neither has.
+    pub fn render(
+        &self,
+        name: &str,
+        format: Format,
+        recipient: &Recipient,
+        contents: &[Content],
+    ) -> Result<Option<String>, Status> {
+        if !self.contains(name) {
+            return Err(Status::not_found(format!("Template {name} not found")));
+        }
+        let chain = locale_chain(recipient.locale.as_deref());
+        let candidates = [name, DEFAULT_TEMPLATE].into_iter().flat_map(|name| {
+            chain
+                .iter()
+                .map(|locale| Some(locale.as_str()))
+                .chain([None])
+                .map(move |locale| file_name(name, locale, format))
+        });
+
+        for file in candidates {
+            let tpl = match self.env.get_template(&file) {
+                Ok(tpl) => tpl,
+                Err(e) if e.kind() == ErrorKind::TemplateNotFound => continue,
+                Err(e) => return Err(render_error(e)),
+            };
+            let contents: Vec<_> = contents.iter().map(ContentView::from).collect();
+            let body = tpl
+                .render(context! { user => recipient, contents => contents })
+                .map_err(render_error)?;
+
+            return Ok(Some(match format {
+                Format::Subject => body.trim().to_string(),
+                Format::Sms => truncate(body.trim(), MAX_SMS_LEN),
+                Format::Html | Format::Text => body,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Tpl<'a> {
+    pub fn new(
+        templates: &'a Templates,
+        name: &'a str,
+        recipient: &'a Recipient,
+        contents: &'a [Content],
+    ) -> Self {
+        Self {
+            templates,
+            name,
+            recipient,
+            contents,
+        }
+    }
+
+    /// The subject in the language of the recipient. If the template has no subject, the given
+    /// one is translated when it is one of the builtin subjects, or used as it is.
+    pub fn to_subject(&self, subject: &str) -> Result<String, Status> {
+        let ret =
+            self.templates
+                .render(self.name, Format::Subject, self.recipient, self.contents)?;
+        Ok(ret.unwrap_or_else(|| {
+            let chain = locale_chain(self.recipient.locale.as_deref());
+            translate(subject, &chain).to_string()
+        }))
+    }
+
+    pub fn to_html(&self) -> Result<String, Status> {
+        self.render(Format::Html)
+    }
+
+    pub fn to_text(&self) -> Result<String, Status> {
+        self.render(Format::Text)
+    }
+
+    pub fn to_sms(&self) -> Result<String, Status> {
+        self.render(Format::Sms)
+    }
+
+    fn render(&self, format: Format) -> Result<String, Status> {
+        let ret = self
+            .templates
+            .render(self.name, format, self.recipient, self.contents)?;
+        Ok(ret.unwrap_or_default())
+    }
+}
+
+impl Content {
+    /// The content in plain text, as it is listed in the builtin templates.
+    pub fn to_body(&self) -> String {
+        let tpl = BUILTIN
+            .env
+            .get_template("content.txt")
+            .expect("builtin templates should exist");
+        tpl.render(context! { content => ContentView::from(self) })
+            .expect("builtin templates should render")
+    }
+}
+
+/// Content as templates see it, with its type in lowercase and its creation time in RFC 3339.
+#[derive(Serialize)]
+struct ContentView<'a> {
+    id: u32,
+    name: &'a str,
+    description: &'a str,
+    publishers: Vec<PublisherView<'a>>,
+    url: &'a str,
+    image: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    created_at: Option<String>,
+    views: u64,
+    likes: u64,
+    dislikes: u64,
+}
+
+#[derive(Serialize)]
+struct PublisherView<'a> {
+    id: u32,
+    name: &'a str,
+    avatar: &'a str,
+}
+
+impl<'a> From<&'a Content> for ContentView<'a> {
+    fn from(content: &'a Content) -> Self {
+        let kind = match content.r#type() {
+            ContentType::Unspecified => "",
+            ContentType::Short => "short",
+            ContentType::Vlog => "vlog",
+            ContentType::Movie => "movie",
+            ContentType::AiGenerated => "ai_generated",
+        };
+        let created_at = content
+            .created_at
+            .as_ref()
+            .and_then(|ts| DateTime::from_timestamp(ts.seconds, ts.nanos as _))
+            .map(|dt| dt.to_rfc3339());
+        Self {
+            id: content.id,
+            name: &content.name,
+            description: &content.description,
+            publishers: content.publishers.iter().map(PublisherView::from).collect(),
+            url: &content.url,
+            image: &content.image,
+            kind,
+            created_at,
+            views: content.views,
+            likes: content.likes,
+            dislikes: content.dislikes,
+        }
+    }
+}
+
+impl<'a> From<&'a Publisher> for PublisherView<'a> {
+    fn from(publisher: &'a Publisher) -> Self {
+        Self {
+            id: publisher.id,
+            name: &publisher.name,
+            avatar: &publisher.avatar,
+        }
+    }
+}
+
+/// `{{ content.created_at | date("%b %d") }}`, the format defaults to `%Y-%m-%d`. Missing
+/// dates render as empty.
+fn date(value: Option<String>, format: Option<String>) -> Result<String, Error> {
+    let Some(value) = value else {
+        return Ok(String::new());
+    };
+    let dt = DateTime::parse_from_rfc3339(&value)
+        .map_err(|e| Error::new(ErrorKind::InvalidOperation, format!("invalid date: {e}")))?;
+    let format = format.as_deref().unwrap_or("%Y-%m-%d");
+    Ok(dt.with_timezone(&Utc).format(format).to_string())
+}
+
+/// `{{ 1234567 | number }}` renders as `1,234,567`.
+fn number(value: i64) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut ret = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    if value < 0 {
+        ret.push('-');
+    }
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            ret.push(',');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+/// `{{ 1234567 | compact }}` renders as `1.2M`.
+fn compact(value: i64) -> String {
+    let abs = value.unsigned_abs() as f64;
+    let sign = if value < 0 { "-" } else { "" };
+    match abs {
+        n if n >= 1e9 => format!("{sign}{:.1}B", n / 1e9),
+        n if n >= 1e6 => format!("{sign}{:.1}M", n / 1e6),
+        n if n >= 1e3 => format!("{sign}{:.1}K", n / 1e3),
+        _ => value.to_string(),
+    }
+}
+
+fn file_name(name: &str, locale: Option<&str>, format: Format) -> String {
+    match locale {
+        Some(locale) => format!("{name}.{locale}.{}", format.extension()),
+        None => format!("{name}.{}", format.extension()),
+    }
+}
+
+/// `zh_TW` as `zh-TW`
+fn normalize(locale: &str) -> String {
+    locale.replace('_', "-")
+}
+
+/// Languages to try in order, from the most specific subtag down to the default locale, say
+/// zh-Hant-TW, zh-Hant, zh, en.
+fn locale_chain(locale: Option<&str>) -> Vec<String> {
+    let mut chain = Vec::new();
+    if let Some(locale) = locale.map(normalize).filter(|l| !l.is_empty()) {
+        let mut tag = locale.as_str();
+        loop {
+            chain.push(tag.to_string());
+            match tag.rsplit_once('-') {
+                Some((parent, _)) => tag = parent,
+                None => break,
+            }
+        }
+    }
+    if !chain.iter().any(|l| l == DEFAULT_LOCALE) {
+        chain.push(DEFAULT_LOCALE.to_string());
+    }
+    chain
+}
+
+/// The builtin subject in the first language of the chain it is translated to, the subject
+/// itself is in the default locale.
+fn translate<'a>(subject: &'a str, chain: &[String]) -> &'a str {
+    let Some((_, translations)) = DEFAULT_SUBJECTS.iter().find(|(s, _)| *s == subject) else {
+        return subject;
+    };
+    chain
+        .iter()
+        .take_while(|locale| *locale != DEFAULT_LOCALE)
+        .find_map(|locale| {
+            translations
+                .iter()
+                .find(|(l, _)| l == locale)
+                .map(|(_, translated)| *translated)
+        })
+        .unwrap_or(subject)
+}
+
+fn truncate(body: &str, max: usize) -> String {
+    if body.chars().count() <= max {
+        return body.to_string();
+    }
+    let mut ret: String = body.chars().take(max - 1).collect();
+    ret.push('…');
+    ret
+}
+
+fn render_error(e: Error) -> Status {
+    warn!("Failed to render template: {:?}", e);
+    Status::internal(format!("Failed to render template: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::Timestamp;
+
+    #[test]
+    fn builtin_templates_should_render() -> Result<()> {
+        let templates = Templates::default();
+        let recipient = Recipient {
+            email: "alice@acme.org".to_string(),
+            name: "Alice".to_string(),
+            ..Default::default()
+        };
+        let contents = [content(1, "Dune <2>"), content(2, "Alien")];
+        let tpl = Tpl::new(&templates, DEFAULT_TEMPLATE, &recipient, &contents);
+
+        let html = tpl.to_html()?;
+        assert!(html.contains("Hi Alice,"));
+        assert!(html.contains("Dune &lt;2&gt;"));
+        assert!(html.contains("1,234,567 views, since 2024-05-01"));
+
+        let text = tpl.to_text()?;
+        assert!(text.starts_with("Hi Alice,\n"));
+        assert!(text.contains("- Dune <2> (1.2M views): https://acme.org/1"));
+
+        assert_eq!(tpl.to_sms()?, "Dune <2> and 1 more: https://acme.org/1");
+        Ok(())
+    }
+
+    #[test]
+    fn named_templates_should_fall_back_to_builtin() -> Result<()> {
+        let mut templates = Templates::default();
+        let source = TemplateSource {
+            sms: Some("{{ user.email }}: {{ contents | length }} new".to_string()),
+            ..Default::default()
+        };
+        templates.add_source("picks", &source)?;
+        let recipient = Recipient {
+            email: "bob@acme.org".to_string(),
+            ..Default::default()
+        };
+        let contents = [content(1, "Dune")];
+        let tpl = Tpl::new(&templates, "picks", &recipient, &contents);
+
+        assert_eq!(tpl.to_sms()?, "bob@acme.org: 1 new");
+        assert!(tpl.to_text()?.starts_with("Hi there,\n"));
+
+        let tpl = Tpl::new(&templates, "missing", &recipient, &contents);
+        assert_eq!(tpl.to_text().unwrap_err().code(), tonic::Code::NotFound);
+        assert!(templates
+            .add("broken", None, Format::Text, "{{ oops".to_string())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn templates_should_personalize_with_fallbacks() -> Result<()> {
+        let mut templates = Templates::default();
+        let source = TemplateSource {
+            text: Some(
+                "{{ user.name | default('there') }}|{{ user.address.city }}|\
+                {% if user.gender == 'female' %}her{% else %}their{% endif %}|\
+                {{ user.finished | length }}"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        templates.add_source("hello", &source)?;
+
+        let mut recipient = Recipient {
+            email: "alice@acme.org".to_string(),
+            ..Default::default()
+        };
+        let tpl = Tpl::new(&templates, "hello", &recipient, &[]);
+        assert_eq!(tpl.to_text()?, "there||their|0");
+
+        recipient.name = "Alice".to_string();
+        recipient.gender = Some("female".to_string());
+        recipient.finished = vec![1, 2];
+        let tpl = Tpl::new(&templates, "hello", &recipient, &[]);
+        assert_eq!(tpl.to_text()?, "Alice||her|2");
+        Ok(())
+    }
+
+    #[test]
+    fn templates_should_fall_back_through_locales() -> Result<()> {
+        let mut templates = Templates::default();
+        let source: TemplateSource = serde_yaml::from_str(
+            r#"
+subject: New picks
+text: Hello
+locales:
+  zh:
+    subject: 新片推荐
+    text: 你好
+  zh_TW:
+    text: 您好
+"#,
+        )?;
+        templates.add_source("picks", &source)?;
+
+        let render = |locale: Option<&str>| -> Result<(String, String), Status> {
+            let recipient = Recipient {
+                locale: locale.map(str::to_string),
+                ..Default::default()
+            };
+            let tpl = Tpl::new(&templates, "picks", &recipient, &[]);
+            Ok((tpl.to_subject("Picks")?, tpl.to_text()?))
+        };
+        assert_eq!(render(Some("zh-TW"))?, ("新片推荐".into(), "您好".into()));
+        assert_eq!(render(Some("zh-CN"))?, ("新片推荐".into(), "你好".into()));
+        assert_eq!(render(Some("fr"))?, ("New picks".into(), "Hello".into()));
+        assert_eq!(render(None)?, ("New picks".into(), "Hello".into()));
+
+        // the builtin template has no subject
+        let recipient = Recipient::default();
+        let tpl = Tpl::new(&templates, DEFAULT_TEMPLATE, &recipient, &[]);
+        assert_eq!(tpl.to_subject("Picks")?, "Picks");
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_templates_should_be_localized() -> Result<()> {
+        let templates = Templates::default();
+        let contents = [content(1, "Dune"), content(2, "Alien")];
+        let render = |locale: &str| -> Result<[String; 4], Status> {
+            let recipient = Recipient {
+                name: "Alice".to_string(),
+                locale: Some(locale.to_string()),
+                ..Default::default()
+            };
+            let tpl = Tpl::new(&templates, DEFAULT_TEMPLATE, &recipient, &contents);
+            Ok([
+                tpl.to_subject("We miss you")?,
+                tpl.to_html()?,
+                tpl.to_text()?,
+                tpl.to_sms()?,
+            ])
+        };
+
+        let [subject, html, text, sms] = render("zh_Hant_TW")?;
+        assert_eq!(subject, "我們想你了");
+        assert!(html.contains("1,234,567 次觀看，發佈於 2024-05-01"));
+        assert!(text.starts_with("Alice，您好：\n"));
+        assert!(text.contains("- Dune（1.2M 次觀看）：https://acme.org/1"));
+        assert_eq!(sms, "Dune等 2 部：https://acme.org/1");
+
+        let [subject, _, text, _] = render("zh-CN")?;
+        assert_eq!(subject, "我们想你了");
+        assert!(text.starts_with("Alice，你好：\n"));
+
+        let [subject, _, text, _] = render("en-GB")?;
+        assert_eq!(subject, "We miss you");
+        assert!(text.starts_with("Hi Alice,\n"));
+
+        // subjects other than the builtin ones are kept as they are
+        let recipient = Recipient {
+            locale: Some("zh".to_string()),
+            ..Default::default()
+        };
+        let tpl = Tpl::new(&templates, DEFAULT_TEMPLATE, &recipient, &[]);
+        assert_eq!(tpl.to_subject("Picks")?, "Picks");
+        assert_eq!(tpl.to_text()?, "你好：\n");
+        Ok(())
+    }
+
+    #[test]
+    fn locale_chain_should_end_with_default() {
+        assert_eq!(
+            locale_chain(Some("zh_Hant_TW")),
+            ["zh-Hant-TW", "zh-Hant", "zh", "en"]
+        );
+        assert_eq!(locale_chain(Some("en-GB")), ["en-GB", "en"]);
+        assert_eq!(locale_chain(Some("")), ["en"]);
+        assert_eq!(locale_chain(None), ["en"]);
+    }
+
+    #[test]
+    fn helpers_should_format() {
+        assert_eq!(number(0), "0");
+        assert_eq!(number(999), "999");
+        assert_eq!(number(-1234567), "-1,234,567");
+        assert_eq!(compact(999), "999");
+        assert_eq!(compact(12_345), "12.3K");
+        assert_eq!(compact(2_500_000_000), "2.5B");
+        assert_eq!(truncate("héllo", 4), "hél…");
+        let date = date(Some("2024-05-01T08:00:00+00:00".to_string()), None);
+        assert_eq!(date.unwrap(), "2024-05-01");
+    }
+
+    fn content(id: u32, name: &str) -> Content {
+        Content {
+            id,
+            name: name.to_string(),
+            url: format!("https://acme.org/{id}"),
+            views: 1234567,
+            created_at: Some(Timestamp {
+                seconds: 1714521600,
+                nanos: 0,
+            }),
+            ..Default::default()
+        }
+    }
+}
@@ -17,6 +17,8 @@ pub struct AuthConfig {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    /// postgres database of the content catalog
+    pub db_url: String,
 }
 
 impl AppConfig {
@@ -38,6 +38,194 @@ pub struct MaterializeRequest {
     #[prost(uint32, tag = "1")]
     pub id: u32,
 }
+/// the content of a materialize request, or why it could not be materialized
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MaterializeResponse {
+    #[prost(oneof = "materialize_response::Result", tags = "1, 2")]
+    pub result: ::core::option::Option<materialize_response::Result>,
+}
+/// Nested message and enum types in `MaterializeResponse`.
+pub mod materialize_response {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Result {
+        #[prost(message, tag = "1")]
+        Content(super::Content),
+        #[prost(message, tag = "2")]
+        Error(super::MaterializeError),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MaterializeError {
+    /// id of the content requested
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    /// grpc status code, NOT_FOUND if there is no such content
+    #[prost(int32, tag = "2")]
+    pub code: i32,
+    #[prost(string, tag = "3")]
+    pub message: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateContentRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub description: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub url: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub image: ::prost::alloc::string::String,
+    #[prost(enumeration = "ContentType", tag = "5")]
+    pub r#type: i32,
+    /// every publisher must exist
+    #[prost(uint32, repeated, tag = "6")]
+    pub publisher_ids: ::prost::alloc::vec::Vec<u32>,
+}
+/// fields not set are left unchanged
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateContentRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, optional, tag = "2")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub description: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub image: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(enumeration = "ContentType", optional, tag = "6")]
+    pub r#type: ::core::option::Option<i32>,
+    /// replaces all the publishers of the content if set
+    #[prost(message, optional, tag = "7")]
+    pub publishers: ::core::option::Option<PublisherIds>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PublisherIds {
+    #[prost(uint32, repeated, tag = "1")]
+    pub ids: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeleteContentRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListContentsRequest {
+    /// any type if not set
+    #[prost(enumeration = "ContentType", tag = "1")]
+    pub r#type: i32,
+    /// contents of any publisher if not set
+    #[prost(uint32, tag = "2")]
+    pub publisher_id: u32,
+    /// created_at >= created_after if set
+    #[prost(message, optional, tag = "3")]
+    pub created_after: ::core::option::Option<::prost_types::Timestamp>,
+    /// created_at < created_before if set
+    #[prost(message, optional, tag = "4")]
+    pub created_before: ::core::option::Option<::prost_types::Timestamp>,
+    /// number of contents in a page, 100 if not set, at most 1000
+    #[prost(uint32, tag = "5")]
+    pub page_size: u32,
+    /// next_cursor of the previous page, empty for the first page
+    #[prost(string, tag = "6")]
+    pub cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListContentsResponse {
+    /// contents ordered by id
+    #[prost(message, repeated, tag = "1")]
+    pub contents: ::prost::alloc::vec::Vec<Content>,
+    /// empty if there are no more pages
+    #[prost(string, tag = "2")]
+    pub next_cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreatePublisherRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub avatar: ::prost::alloc::string::String,
+}
+/// fields not set are left unchanged
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdatePublisherRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, optional, tag = "2")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub avatar: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeletePublisherRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPublishersRequest {
+    /// number of publishers in a page, 100 if not set, at most 1000
+    #[prost(uint32, tag = "1")]
+    pub page_size: u32,
+    /// next_cursor of the previous page, empty for the first page
+    #[prost(string, tag = "2")]
+    pub cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPublishersResponse {
+    /// publishers ordered by id
+    #[prost(message, repeated, tag = "1")]
+    pub publishers: ::prost::alloc::vec::Vec<Publisher>,
+    /// empty if there are no more pages
+    #[prost(string, tag = "2")]
+    pub next_cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeleteResponse {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecommendRequest {
+    /// contents the user has watched recently, they are never recommended
+    #[prost(uint32, repeated, tag = "1")]
+    pub recent_watched: ::prost::alloc::vec::Vec<u32>,
+    /// contents the user has finished, they are never recommended
+    #[prost(uint32, repeated, tag = "2")]
+    pub finished: ::prost::alloc::vec::Vec<u32>,
+    /// number of contents to recommend, 10 if not set, at most 100
+    #[prost(uint32, tag = "3")]
+    pub limit: u32,
+    /// contents of any type if empty
+    #[prost(enumeration = "ContentType", repeated, tag = "4")]
+    pub types: ::prost::alloc::vec::Vec<i32>,
+    /// only contents created at or after it if set
+    #[prost(message, optional, tag = "5")]
+    pub created_after: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecommendResponse {
+    /// the most popular contents first, see Metadata.Recommend
+    #[prost(message, repeated, tag = "1")]
+    pub contents: ::prost::alloc::vec::Vec<Content>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SearchRequest {
+    /// words to match against the name, description and publishers of contents, in web search
+    /// syntax: "quoted phrases", or, and -excluded words
+    #[prost(string, tag = "1")]
+    pub query: ::prost::alloc::string::String,
+    /// contents of any type if empty
+    #[prost(enumeration = "ContentType", repeated, tag = "2")]
+    pub types: ::prost::alloc::vec::Vec<i32>,
+    #[prost(enumeration = "SearchOrder", tag = "3")]
+    pub order: i32,
+    /// number of contents to return, 20 if not set, at most 100
+    #[prost(uint32, tag = "4")]
+    pub limit: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SearchResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub contents: ::prost::alloc::vec::Vec<Content>,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ContentType {
@@ -73,6 +261,39 @@ impl ContentType {
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SearchOrder {
+    /// the best matches first, matches in names weigh more than in descriptions, which weigh more
+    /// than in publisher names
+    Relevance = 0,
+    /// the most viewed first
+    Views = 1,
+    /// the most recently created first
+    Recency = 2,
+}
+impl SearchOrder {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Relevance => "SEARCH_ORDER_RELEVANCE",
+            Self::Views => "SEARCH_ORDER_VIEWS",
+            Self::Recency => "SEARCH_ORDER_RECENCY",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SEARCH_ORDER_RELEVANCE" => Some(Self::Relevance),
+            "SEARCH_ORDER_VIEWS" => Some(Self::Views),
+            "SEARCH_ORDER_RECENCY" => Some(Self::Recency),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod metadata_client {
     #![allow(
@@ -163,11 +384,12 @@ pub mod metadata_client {
             self.inner = self.inner.max_encoding_message_size(limit);
             self
         }
+        /// contents are returned in the order of the requests, each request gets a response
         pub async fn materialize(
             &mut self,
             request: impl tonic::IntoStreamingRequest<Message = super::MaterializeRequest>,
         ) -> std::result::Result<
-            tonic::Response<tonic::codec::Streaming<super::Content>>,
+            tonic::Response<tonic::codec::Streaming<super::MaterializeResponse>>,
             tonic::Status,
         > {
             self.inner.ready().await.map_err(|e| {
@@ -180,6 +402,151 @@ pub mod metadata_client {
                 .insert(GrpcMethod::new("metadata.Metadata", "Materialize"));
             self.inner.streaming(req, path, codec).await
         }
+        pub async fn create_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/CreateContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "CreateContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/UpdateContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "UpdateContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn delete_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/DeleteContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "DeleteContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_contents(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListContentsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListContentsResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/ListContents");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "ListContents"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn create_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/CreatePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "CreatePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/UpdatePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "UpdatePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// the publisher is also removed from the contents it published
+        pub async fn delete_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeletePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/DeletePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "DeletePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_publishers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListPublishersRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListPublishersResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/ListPublishers");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "ListPublishers"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// contents the user has not watched, ranked by popularity, which is ln(1 + views) weighted by
+        /// the smoothed like ratio (likes + 1) / (likes + dislikes + 2)
+        pub async fn recommend(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecommendRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecommendResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/Recommend");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "Recommend"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn search(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SearchRequest>,
+        ) -> std::result::Result<tonic::Response<super::SearchResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/Search");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "Search"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -197,13 +564,57 @@ pub mod metadata_server {
     pub trait Metadata: std::marker::Send + std::marker::Sync + 'static {
         /// Server streaming response type for the Materialize method.
         type MaterializeStream: tonic::codegen::tokio_stream::Stream<
-                Item = std::result::Result<super::Content, tonic::Status>,
+                Item = std::result::Result<super::MaterializeResponse, tonic::Status>,
             > + std::marker::Send
             + 'static;
+        /// contents are returned in the order of the requests, each request gets a response
         async fn materialize(
             &self,
             request: tonic::Request<tonic::Streaming<super::MaterializeRequest>>,
         ) -> std::result::Result<tonic::Response<Self::MaterializeStream>, tonic::Status>;
+        async fn create_content(
+            &self,
+            request: tonic::Request<super::CreateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status>;
+        async fn update_content(
+            &self,
+            request: tonic::Request<super::UpdateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status>;
+        async fn delete_content(
+            &self,
+            request: tonic::Request<super::DeleteContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status>;
+        async fn list_contents(
+            &self,
+            request: tonic::Request<super::ListContentsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListContentsResponse>, tonic::Status>;
+        async fn create_publisher(
+            &self,
+            request: tonic::Request<super::CreatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status>;
+        async fn update_publisher(
+            &self,
+            request: tonic::Request<super::UpdatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status>;
+        /// the publisher is also removed from the contents it published
+        async fn delete_publisher(
+            &self,
+            request: tonic::Request<super::DeletePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status>;
+        async fn list_publishers(
+            &self,
+            request: tonic::Request<super::ListPublishersRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListPublishersResponse>, tonic::Status>;
+        /// contents the user has not watched, ranked by popularity, which is ln(1 + views) weighted by
+        /// the smoothed like ratio (likes + 1) / (likes + dislikes + 2)
+        async fn recommend(
+            &self,
+            request: tonic::Request<super::RecommendRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecommendResponse>, tonic::Status>;
+        async fn search(
+            &self,
+            request: tonic::Request<super::SearchRequest>,
+        ) -> std::result::Result<tonic::Response<super::SearchResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct MetadataServer<T> {
@@ -282,7 +693,7 @@ pub mod metadata_server {
                     #[allow(non_camel_case_types)]
                     struct MaterializeSvc<T: Metadata>(pub Arc<T>);
                     impl<T: Metadata> tonic::server::StreamingService<super::MaterializeRequest> for MaterializeSvc<T> {
-                        type Response = super::Content;
+                        type Response = super::MaterializeResponse;
                         type ResponseStream = T::MaterializeStream;
                         type Future =
                             BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
@@ -318,6 +729,401 @@ pub mod metadata_server {
                     };
                     Box::pin(fut)
                 }
+                "/metadata.Metadata/CreateContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::CreateContentRequest> for CreateContentSvc<T> {
+                        type Response = super::Content;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::create_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreateContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/UpdateContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::UpdateContentRequest> for UpdateContentSvc<T> {
+                        type Response = super::Content;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::update_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/DeleteContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::DeleteContentRequest> for DeleteContentSvc<T> {
+                        type Response = super::DeleteResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::delete_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = DeleteContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/ListContents" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListContentsSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::ListContentsRequest> for ListContentsSvc<T> {
+                        type Response = super::ListContentsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListContentsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::list_contents(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListContentsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/CreatePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreatePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::CreatePublisherRequest>
+                        for CreatePublisherSvc<T>
+                    {
+                        type Response = super::Publisher;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreatePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::create_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreatePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/UpdatePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdatePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::UpdatePublisherRequest>
+                        for UpdatePublisherSvc<T>
+                    {
+                        type Response = super::Publisher;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdatePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::update_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdatePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/DeletePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeletePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::DeletePublisherRequest>
+                        for DeletePublisherSvc<T>
+                    {
+                        type Response = super::DeleteResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeletePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::delete_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = DeletePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/ListPublishers" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListPublishersSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::ListPublishersRequest>
+                        for ListPublishersSvc<T>
+                    {
+                        type Response = super::ListPublishersResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListPublishersRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::list_publishers(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListPublishersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/Recommend" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecommendSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::RecommendRequest> for RecommendSvc<T> {
+                        type Response = super::RecommendResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecommendRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Metadata>::recommend(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecommendSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/Search" => {
+                    #[allow(non_camel_case_types)]
+                    struct SearchSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::SearchRequest> for SearchSvc<T> {
+                        type Response = super::SearchResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SearchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as Metadata>::search(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SearchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     let mut response = http::Response::new(empty_body());
                     let headers = response.headers_mut();
@@ -1,40 +1,144 @@
 use anyhow::Result;
 use crm_metadata::pb::metadata_client::MetadataClient;
-use crm_metadata::pb::MaterializeRequest;
+use crm_metadata::pb::{
+    ContentType, CreateContentRequest, CreatePublisherRequest, DeleteContentRequest,
+    DeletePublisherRequest, ListContentsRequest, ListPublishersRequest, MaterializeRequest,
+    PublisherIds, UpdateContentRequest,
+};
 use crm_metadata::{AppConfig, MetadataService};
+use sqlx_db_tester::TestPg;
 use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::time::sleep;
 use tokio_stream::StreamExt;
 use tonic::transport::Server;
-use tonic::Request;
+use tonic::{Code, Request};
 
 #[tokio::test]
 async fn test_metadata() -> Result<()> {
-    let addr = start_server().await?;
+    let config = AppConfig::try_load()?;
+    let (_tdb, addr) = start_server(config.server.port).await?;
     let mut client = MetadataClient::connect(format!("http://{addr}")).await?;
     let stream = tokio_stream::iter(vec![
         MaterializeRequest { id: 1 },
         MaterializeRequest { id: 2 },
         MaterializeRequest { id: 3 },
+        MaterializeRequest { id: 999 },
     ]);
     let request = Request::new(stream);
     let response = client.materialize(request).await?.into_inner();
     let ret: Vec<_> = response
-        .then(|item| async move { item.unwrap() })
+        .then(|item| async move { item.unwrap().into_result() })
         .collect()
         .await;
 
-    assert_eq!(ret.len(), 3);
+    assert_eq!(ret.len(), 4);
+    let names: Vec<_> = ret[..3]
+        .iter()
+        .map(|v| v.as_ref().unwrap().name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["The Long Road", "Morning Coffee", "Behind the Scenes"]
+    );
+    let status = ret[3].as_ref().unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
 
     Ok(())
 }
 
-async fn start_server() -> Result<SocketAddr> {
+#[tokio::test]
+async fn contents_and_publishers_could_be_managed() -> Result<()> {
     let config = AppConfig::try_load()?;
-    let addr = format!("[::1]:{}", config.server.port).parse()?;
+    let (_tdb, addr) = start_server(config.server.port + 1).await?;
+    let mut client = MetadataClient::connect(format!("http://{addr}")).await?;
+
+    let publisher = client
+        .create_publisher(CreatePublisherRequest {
+            name: "New Studio".to_string(),
+            avatar: "https://placehold.co/400x400?text=new".to_string(),
+        })
+        .await?
+        .into_inner();
+    let content = client
+        .create_content(CreateContentRequest {
+            name: "Premiere".to_string(),
+            url: "https://acme.org/contents/premiere".to_string(),
+            r#type: ContentType::Short as _,
+            publisher_ids: vec![publisher.id, 1],
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    assert_eq!(content.name, "Premiere");
+    let ids: Vec<_> = content.publishers.iter().map(|p| p.id).collect();
+    assert_eq!(ids, [1, publisher.id]);
+
+    let content = client
+        .update_content(UpdateContentRequest {
+            id: content.id,
+            description: Some("The very first one".to_string()),
+            publishers: Some(PublisherIds {
+                ids: vec![publisher.id],
+            }),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    assert_eq!(content.name, "Premiere");
+    assert_eq!(content.description, "The very first one");
+    assert_eq!(content.publishers, vec![publisher.clone()]);
+
+    // shorts of the new publisher
+    let ret = client
+        .list_contents(ListContentsRequest {
+            r#type: ContentType::Short as _,
+            publisher_id: publisher.id,
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    assert_eq!(ret.contents, vec![content.clone()]);
+    assert!(ret.next_cursor.is_empty());
+
+    // deleting the publisher keeps its contents
+    client
+        .delete_publisher(DeletePublisherRequest { id: publisher.id })
+        .await?;
+    let ret = client
+        .list_publishers(ListPublishersRequest::default())
+        .await?
+        .into_inner();
+    assert!(ret.publishers.iter().all(|p| p.id != publisher.id));
+
+    client
+        .delete_content(DeleteContentRequest { id: content.id })
+        .await?;
+    let status = client
+        .delete_content(DeleteContentRequest { id: content.id })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let status = client
+        .create_content(CreateContentRequest {
+            name: "Orphan".to_string(),
+            url: "https://acme.org/contents/orphan".to_string(),
+            publisher_ids: vec![999],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+async fn start_server(port: u16) -> Result<(TestPg, SocketAddr)> {
+    let addr = format!("[::1]:{}", port).parse()?;
 
-    let svc = MetadataService::new(config).await.into_server();
+    let (tdb, svc) = MetadataService::new_for_test().await?;
+    let svc = svc.into_server();
 
     tokio::spawn(async move {
         Server::builder()
@@ -46,5 +150,5 @@ async fn start_server() -> Result<SocketAddr> {
 
     sleep(Duration::from_micros(1)).await;
 
-    Ok(addr)
+    Ok((tdb, addr))
 }
@@ -47,6 +47,7 @@ impl EmailMessage {
             recipients: vec![SafeEmail().fake()],
             subject: "Hello".to_string(),
             body: "Hello, world!".to_string(),
+            html_body: "<p>Hello, world!</p>".to_string(),
         }
     }
 }
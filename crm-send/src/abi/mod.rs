@@ -1,9 +1,10 @@
+#![allow(clippy::result_large_err)]
+
 use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::Utc;
-use crm_metadata::pb::Content;
 use crm_metadata::Tpl;
 use prost_types::Timestamp;
 use tokio::sync::mpsc;
@@ -15,7 +16,7 @@ use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::pb::send_request::Msg;
-use crate::pb::{EmailMessage, SendResponse};
+use crate::pb::{EmailMessage, InAppMessage, SendResponse, SmsMessage};
 use crate::NotificationServiceInner;
 use crate::{
     pb::{notification_server::NotificationServer, SendRequest},
@@ -84,22 +85,45 @@ impl Deref for NotificationService {
 }
 
 impl SendRequest {
+    /// Email with both the html and plain text bodies of the template.
     pub fn new(
         subject: String,
         sender: String,
         recipients: &[String],
-        contents: &[Content],
-    ) -> Self {
-        let tpl = Tpl(contents);
+        tpl: &Tpl,
+    ) -> Result<Self, Status> {
         let msg = Msg::Email(EmailMessage {
             message_id: Uuid::new_v4().to_string(),
             subject,
             sender,
             recipients: recipients.to_vec(),
-            body: tpl.to_body(),
+            body: tpl.to_text()?,
+            html_body: tpl.to_html()?,
         });
 
-        SendRequest { msg: Some(msg) }
+        Ok(SendRequest { msg: Some(msg) })
+    }
+
+    /// Sms with the short form of the template.
+    pub fn new_sms(sender: String, recipients: &[String], tpl: &Tpl) -> Result<Self, Status> {
+        Ok(SmsMessage {
+            message_id: Uuid::new_v4().to_string(),
+            sender,
+            recipients: recipients.to_vec(),
+            body: tpl.to_sms()?,
+        }
+        .into())
+    }
+
+    /// In-app message with the short form of the template.
+    pub fn new_in_app(title: String, device_id: String, tpl: &Tpl) -> Result<Self, Status> {
+        Ok(InAppMessage {
+            message_id: Uuid::new_v4().to_string(),
+            device_id,
+            title,
+            body: tpl.to_sms()?,
+        }
+        .into())
     }
 }
 
@@ -14,9 +14,12 @@ pub struct EmailMessage {
     /// recipients of the email
     #[prost(string, repeated, tag = "4")]
     pub recipients: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
-    /// body of the email
+    /// plain text body of the email
     #[prost(string, tag = "5")]
     pub body: ::prost::alloc::string::String,
+    /// html body of the email, the email is plain text only if empty
+    #[prost(string, tag = "6")]
+    pub html_body: ::prost::alloc::string::String,
 }
 /// sms message to be sent
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -4,6 +4,7 @@ use crm_send::pb::{EmailMessage, InAppMessage, SendRequest, SmsMessage};
 use crm_send::{AppConfig, NotificationService};
 use futures::StreamExt;
 use std::net::SocketAddr;
+use tonic::transport::server::TcpIncoming;
 use tonic::transport::Server;
 use tonic::Request;
 
@@ -39,11 +40,13 @@ async fn start_server() -> Result<SocketAddr> {
     let addr = format!("[::1]:{}", config.server.port).parse()?;
 
     let svc = NotificationService::new(config).into_server();
+    // bind before returning so that clients could connect right away
+    let incoming = TcpIncoming::new(addr, true, None).map_err(|e| anyhow::anyhow!(e))?;
 
     tokio::spawn(async move {
         Server::builder()
             .add_service(svc)
-            .serve(addr)
+            .serve_with_incoming(incoming)
             .await
             .expect("Failed to start server");
     });
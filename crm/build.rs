@@ -7,11 +7,59 @@ fn main() -> Result<()> {
     let builder = tonic_build::configure();
     builder
         .out_dir("src/pb")
-        .with_derive_builder(&["WelcomeRequest", "RecallRequest", "RemindRequest"], None)
+        .extern_path(".notification", "::crm_send::pb")
+        .extern_path(".user_stats", "::user_stat::pb")
+        .with_derive_builder(
+            &[
+                "WelcomeRequest",
+                "RecallRequest",
+                "RemindRequest",
+                "Variant",
+                "RunCampaignRequest",
+                "RunScheduleRequest",
+                "GetCampaignRequest",
+                "WatchCampaignRequest",
+            ],
+            None,
+        )
         .with_field_attributes(
-            &["WelcomeRequest.content_ids"],
+            &[
+                "WelcomeRequest.content_ids",
+                "RecallRequest.content_ids",
+                "Variant.content_ids",
+            ],
             &[r#"#[builder(setter(each(name = "content_id", into)))]"#],
         )
+        .with_field_attributes(
+            &[
+                "WelcomeRequest.variants",
+                "RecallRequest.variants",
+                "RemindRequest.variants",
+            ],
+            &[r#"#[builder(setter(each(name = "variant")))]"#],
+        )
+        .with_field_attributes(
+            &[
+                "WelcomeRequest.channels",
+                "RecallRequest.channels",
+                "RemindRequest.channels",
+            ],
+            &[r#"#[builder(setter(each(name = "channel")))]"#],
+        )
+        .with_field_attributes(
+            &[
+                "Variant.name",
+                "Variant.subject",
+                "Variant.template",
+                "RunCampaignRequest.id",
+                "RunCampaignRequest.name",
+                "RunScheduleRequest.id",
+                "RunScheduleRequest.name",
+                "GetCampaignRequest.id",
+                "WatchCampaignRequest.id",
+            ],
+            &[r#"#[builder(setter(into))]"#],
+        )
         .compile_protos(
             &["../protos/crm/messages.proto", "../protos/crm/rpc.proto"],
             &["../protos"],
@@ -0,0 +1,20 @@
+-- campaigns crm has run, keyed by the request id
+CREATE TABLE campaigns(
+    id text NOT NULL PRIMARY KEY,
+    -- what the campaign is built from, so that its id can't be reused for a different campaign
+    fingerprint text NOT NULL,
+    -- NULL until the campaign has finished
+    suppressed bigint,
+    -- final progress of the campaign, an encoded crm.Campaign, NULL until the campaign has finished
+    progress bytea,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at timestamptz
+);
+
+-- recipients a campaign has been delivered to
+CREATE TABLE campaign_recipients(
+    campaign_id text NOT NULL REFERENCES campaigns(id),
+    email varchar(128) NOT NULL,
+    sent_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (campaign_id, email)
+);
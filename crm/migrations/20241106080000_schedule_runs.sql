@@ -0,0 +1,5 @@
+-- last scheduled run of each schedule, so that a restart does not run it twice
+CREATE TABLE schedule_runs(
+    name text NOT NULL PRIMARY KEY,
+    last_run_at timestamptz NOT NULL
+);
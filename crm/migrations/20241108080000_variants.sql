@@ -0,0 +1,2 @@
+-- variant of the campaign a recipient has been assigned to, NULL if the campaign has no variants
+ALTER TABLE campaign_recipients ADD COLUMN variant text;
@@ -0,0 +1,268 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use futures::{stream, Stream};
+use tokio::sync::watch;
+use tonic::Status;
+use user_stat::pb::NotificationChannel;
+
+use crate::pb::{Campaign, CampaignStatus};
+
+/// How long a failed campaign is kept after it has finished, a completed campaign is evicted
+/// as soon as the ledger holds its final progress.
+const FINISHED_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Progress of the campaigns run by this process.
+pub struct CampaignTracker {
+    campaigns: Mutex<HashMap<String, Tracked>>,
+    ttl: Duration,
+}
+
+struct Tracked {
+    progress: CampaignProgress,
+    fingerprint: String,
+}
+
+pub enum Tracking {
+    /// the campaign is tracked from now on
+    New(CampaignProgress),
+    /// the campaign of the same id is still running, or has completed
+    Existing(Campaign),
+}
+
+/// Handle to update the progress of a campaign, every update is pushed to its watchers.
+#[derive(Clone)]
+pub struct CampaignProgress {
+    tx: watch::Sender<Campaign>,
+    finished_at: Arc<OnceLock<Instant>>,
+}
+
+impl Default for CampaignTracker {
+    fn default() -> Self {
+        Self {
+            campaigns: Default::default(),
+            ttl: FINISHED_TTL,
+        }
+    }
+}
+
+impl CampaignTracker {
+    /// Track a new campaign, or return the progress of the campaign with the same id unless it
+    /// has failed, in which case it is tracked again to be retried. The id can't be reused for a
+    /// campaign with a different fingerprint. Campaigns finished longer than the TTL ago are
+    /// dropped along the way.
+    pub fn track(&self, campaign: Campaign, fingerprint: &str) -> Result<Tracking, Status> {
+        let mut campaigns = self.campaigns.lock().unwrap();
+        campaigns.retain(|_, tracked| !tracked.progress.expired(self.ttl));
+        if let Some(tracked) = campaigns.get(&campaign.id) {
+            if tracked.fingerprint != fingerprint {
+                return Err(already_exists(&campaign.id));
+            }
+            let current = tracked.progress.get();
+            if current.status() != CampaignStatus::Failed {
+                return Ok(Tracking::Existing(current));
+            }
+        }
+
+        let (tx, _) = watch::channel(campaign.clone());
+        let progress = CampaignProgress {
+            tx,
+            finished_at: Default::default(),
+        };
+        let tracked = Tracked {
+            progress: progress.clone(),
+            fingerprint: fingerprint.to_string(),
+        };
+        campaigns.insert(campaign.id, tracked);
+        Ok(Tracking::New(progress))
+    }
+
+    /// Stop tracking the campaign once it has finished, its final progress is in the ledger.
+    pub fn evict(&self, id: &str) {
+        let mut campaigns = self.campaigns.lock().unwrap();
+        if campaigns
+            .get(id)
+            .is_some_and(|tracked| tracked.progress.get().is_finished())
+        {
+            campaigns.remove(id);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Result<Campaign, Status> {
+        let campaigns = self.campaigns.lock().unwrap();
+        let tracked = campaigns.get(id).ok_or_else(|| not_found(id))?;
+        Ok(tracked.progress.get())
+    }
+
+    /// Stream the current progress of the campaign and every update after it, until the
+    /// campaign has finished.
+    pub fn watch(
+        &self,
+        id: &str,
+    ) -> Result<impl Stream<Item = Result<Campaign, Status>> + Send + 'static, Status> {
+        let rx = self
+            .campaigns
+            .lock()
+            .unwrap()
+            .get(id)
+            .ok_or_else(|| not_found(id))?
+            .progress
+            .tx
+            .subscribe();
+
+        Ok(stream::unfold(Some((rx, true)), |state| async move {
+            let (mut rx, first) = state?;
+            if !first {
+                rx.changed().await.ok()?;
+            }
+            let campaign = rx.borrow_and_update().clone();
+            let next = (!campaign.is_finished()).then_some((rx, false));
+            Some((Ok(campaign), next))
+        }))
+    }
+}
+
+impl CampaignProgress {
+    pub fn get(&self) -> Campaign {
+        self.tx.borrow().clone()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut Campaign)) {
+        self.tx.send_modify(f);
+    }
+
+    /// A message of the variant has been sent via the channel, the variant is empty if the
+    /// campaign has no variants.
+    pub fn sent(&self, channel: NotificationChannel, variant: &str) {
+        self.update(|c| {
+            c.sent += 1;
+            c.channels.entry(channel_name(channel)).or_default().sent += 1;
+            if !variant.is_empty() {
+                c.variants.entry(variant.to_string()).or_default().sent += 1;
+            }
+        });
+    }
+
+    /// A targeted user is not notified, so it is neither sent nor failed.
+    pub fn skipped(&self) {
+        self.update(|c| c.skipped += 1);
+    }
+
+    pub fn failed(&self, channel: NotificationChannel, variant: &str, n: u64) {
+        if n == 0 {
+            return;
+        }
+        self.update(|c| {
+            c.failed += n;
+            c.channels.entry(channel_name(channel)).or_default().failed += n;
+            if !variant.is_empty() {
+                c.variants.entry(variant.to_string()).or_default().failed += n;
+            }
+        });
+    }
+
+    pub fn finish(&self, ret: &Result<(), Status>) {
+        self.update(|c| match ret {
+            Ok(()) => c.status = CampaignStatus::Completed as _,
+            Err(e) => {
+                c.status = CampaignStatus::Failed as _;
+                c.error = e.message().to_string();
+            }
+        });
+        let _ = self.finished_at.set(Instant::now());
+    }
+
+    fn expired(&self, ttl: Duration) -> bool {
+        self.finished_at.get().is_some_and(|at| at.elapsed() >= ttl)
+    }
+}
+
+impl Campaign {
+    pub fn new(id: impl Into<String>, status: CampaignStatus) -> Self {
+        Self {
+            id: id.into(),
+            status: status as _,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status(),
+            CampaignStatus::Completed | CampaignStatus::Failed
+        )
+    }
+}
+
+fn channel_name(channel: NotificationChannel) -> String {
+    match channel {
+        NotificationChannel::Email => "email",
+        NotificationChannel::Sms => "sms",
+        NotificationChannel::InApp => "in_app",
+        NotificationChannel::Unspecified => "unspecified",
+    }
+    .to_string()
+}
+
+fn not_found(id: &str) -> Status {
+    Status::not_found(format!("Campaign {id} not found"))
+}
+
+pub(super) fn already_exists(id: &str) -> Status {
+    Status::already_exists(format!(
+        "Campaign {id} already exists with different parameters"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn finished_campaigns_should_be_dropped_after_ttl() {
+        let tracker = CampaignTracker {
+            campaigns: Default::default(),
+            ttl: Duration::ZERO,
+        };
+        let Ok(Tracking::New(failed)) =
+            tracker.track(Campaign::new("c1", CampaignStatus::Pending), "f")
+        else {
+            panic!("c1 should be tracked");
+        };
+        let Ok(Tracking::New(_running)) =
+            tracker.track(Campaign::new("c2", CampaignStatus::Pending), "f")
+        else {
+            panic!("c2 should be tracked");
+        };
+        failed.finish(&Err(Status::internal("boom")));
+        assert_eq!(tracker.get("c1").unwrap().status(), CampaignStatus::Failed);
+
+        // tracking another campaign sweeps the finished ones only
+        assert!(tracker
+            .track(Campaign::new("c3", CampaignStatus::Pending), "f")
+            .is_ok());
+        assert_eq!(tracker.get("c1").unwrap_err().code(), Code::NotFound);
+        assert!(tracker.get("c2").is_ok());
+    }
+
+    #[test]
+    fn completed_campaigns_should_be_evicted() {
+        let tracker = CampaignTracker::default();
+        let Ok(Tracking::New(progress)) =
+            tracker.track(Campaign::new("c1", CampaignStatus::Pending), "f")
+        else {
+            panic!("c1 should be tracked");
+        };
+        // still running
+        tracker.evict("c1");
+        assert!(tracker.get("c1").is_ok());
+
+        progress.finish(&Ok(()));
+        tracker.evict("c1");
+        assert_eq!(tracker.get("c1").unwrap_err().code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,191 @@
+#![allow(clippy::result_large_err)]
+
+use chrono::{DateTime, Duration, Utc};
+use tonic::{Response, Status};
+use user_stat::pb::{Filter, Gender, IdOperator, NotificationChannel, QueryRequest};
+
+use super::{config_fingerprint, Contents, Message};
+use crate::config::{
+    CampaignConfig, ChannelConfig, ContentSelection, GenderConfig, IdOperatorConfig, SegmentFilter,
+};
+use crate::pb::{RunCampaignRequest, RunCampaignResponse};
+use crate::CrmService;
+
+impl CrmService {
+    pub async fn run_campaign(
+        &self,
+        req: RunCampaignRequest,
+    ) -> Result<Response<RunCampaignResponse>, Status> {
+        let (query, message) = self.defined_campaign(&req.name)?;
+        let fingerprint = self.config.campaigns[&req.name].fingerprint(&req.name)?;
+        let (id, suppressed) = self.launch(req.id, fingerprint, query, message).await?;
+
+        Ok(Response::new(RunCampaignResponse { id, suppressed }))
+    }
+
+    /// Build the query and message of a campaign defined in the config.
+    pub(super) fn defined_campaign(&self, name: &str) -> Result<(QueryRequest, Message), Status> {
+        let Some(campaign) = self.config.campaigns.get(name) else {
+            return Err(Status::not_found(format!("Campaign {name} is not defined")));
+        };
+        campaign.campaign(Utc::now())
+    }
+}
+
+impl CampaignConfig {
+    pub(super) fn fingerprint(&self, name: &str) -> Result<String, Status> {
+        config_fingerprint(&format!("campaign {name}"), self)
+    }
+
+    pub(super) fn campaign(&self, now: DateTime<Utc>) -> Result<(QueryRequest, Message), Status> {
+        let query = QueryRequest {
+            filter: Some(Filter::and(self.segment.iter().map(|f| f.to_filter(now)))),
+            ..Default::default()
+        };
+        let contents = match &self.contents {
+            ContentSelection::Ids(content_ids) => Contents::Ids(content_ids.clone()),
+            ContentSelection::Unfinished => Contents::Unfinished,
+            ContentSelection::Recommended(n) => Contents::Recommended(*n),
+        };
+        let mut message = Message::new(&self.subject, contents, &channel_ids(&self.channels))?;
+        if let Some(template) = &self.template {
+            message = message.with_template(template);
+        }
+
+        Ok((query, message))
+    }
+}
+
+impl SegmentFilter {
+    fn to_filter(&self, now: DateTime<Utc>) -> Filter {
+        let days_ago = |days: &Option<u32>| days.map(|d| now - Duration::days(d as _));
+        match self {
+            SegmentFilter::Between {
+                column,
+                from_days_ago,
+                to_days_ago,
+            } => Filter::time(column, days_ago(from_days_ago), days_ago(to_days_ago)),
+            SegmentFilter::Null(column) => Filter::null(column),
+            SegmentFilter::NotNull(column) => Filter::not(Filter::null(column)),
+            SegmentFilter::Ids { column, op, ids } => Filter::ids(column, (*op).into(), ids),
+            SegmentFilter::Gender(gender) => Filter::gender((*gender).into()),
+        }
+    }
+}
+
+pub(super) fn channel_ids(channels: &[ChannelConfig]) -> Vec<i32> {
+    channels
+        .iter()
+        .map(|c| NotificationChannel::from(*c) as i32)
+        .collect()
+}
+
+impl From<IdOperatorConfig> for IdOperator {
+    fn from(op: IdOperatorConfig) -> Self {
+        match op {
+            IdOperatorConfig::Contains => IdOperator::Contains,
+            IdOperatorConfig::Overlaps => IdOperator::Overlaps,
+            IdOperatorConfig::ContainedBy => IdOperator::ContainedBy,
+        }
+    }
+}
+
+impl From<GenderConfig> for Gender {
+    fn from(gender: GenderConfig) -> Self {
+        match gender {
+            GenderConfig::Female => Gender::Female,
+            GenderConfig::Male => Gender::Male,
+            GenderConfig::Unknown => Gender::Unknown,
+        }
+    }
+}
+
+impl From<ChannelConfig> for NotificationChannel {
+    fn from(channel: ChannelConfig) -> Self {
+        match channel {
+            ChannelConfig::Email => NotificationChannel::Email,
+            ChannelConfig::Sms => NotificationChannel::Sms,
+            ChannelConfig::InApp => NotificationChannel::InApp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppConfig;
+    use anyhow::Result;
+    use user_stat::pb::filter::Expr;
+
+    #[test]
+    fn defined_campaigns_should_load() -> Result<()> {
+        let config = AppConfig::try_load()?;
+        let campaign = &config.campaigns["weekly_picks"];
+        assert!(matches!(campaign.contents, ContentSelection::Ids(ref ids) if ids == &[1, 2, 3]));
+        assert_eq!(
+            campaign.channels,
+            [ChannelConfig::InApp, ChannelConfig::Email]
+        );
+        assert_eq!(campaign.schedule.as_deref(), Some("0 0 9 * * Mon"));
+
+        let campaign = &config.campaigns["continue_watching"];
+        assert!(matches!(campaign.contents, ContentSelection::Unfinished));
+        Ok(())
+    }
+
+    #[test]
+    fn segment_should_convert_to_filter() -> Result<()> {
+        let campaign: CampaignConfig = serde_yaml::from_str(
+            r#"
+subject: Hello
+segment:
+  - between:
+      column: created_at
+      from_days_ago: 7
+      to_days_ago: 6
+  - not_null: started_but_not_finished
+  - ids:
+      column: viewed_but_not_started
+      op: overlaps
+      ids: [1, 2]
+  - gender: male
+contents:
+  ids: [1]
+"#,
+        )?;
+        let now = Utc::now();
+        let (query, message) = campaign.campaign(now)?;
+        assert_eq!(message.variants[0].subject, "Hello");
+        assert!(matches!(message.variants[0].contents, Contents::Ids(ref ids) if ids == &[1]));
+        assert_eq!(message.channels, [NotificationChannel::Email]);
+        assert!(query.timestamps.is_empty() && query.ids.is_empty());
+
+        let Some(Expr::And(list)) = query.filter.and_then(|f| f.expr) else {
+            panic!("segment should be a conjunction of its filters");
+        };
+        assert_eq!(
+            list.filters,
+            [
+                Filter::time(
+                    "created_at",
+                    Some(now - Duration::days(7)),
+                    Some(now - Duration::days(6))
+                ),
+                Filter::not(Filter::null("started_but_not_finished")),
+                Filter::ids("viewed_but_not_started", IdOperator::Overlaps, &[1, 2]),
+                Filter::gender(Gender::Male),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn template_should_be_named() -> Result<()> {
+        let campaign: CampaignConfig = serde_yaml::from_str(
+            "{ subject: Hi, segment: [], contents: unfinished, channels: [sms], template: Hi }",
+        )?;
+        let (_, message) = campaign.campaign(Utc::now())?;
+        assert_eq!(message.variants[0].template, "Hi");
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, Utc};
+use tonic::Status;
+use user_stat::pb::{Filter, NotificationChannel, QueryRequest};
+
+use crate::config::FrequencyCapConfig;
+use crate::CrmService;
+
+impl FrequencyCapConfig {
+    /// Filter out the users notified via the channel within the cap window, None if the channel
+    /// is not capped.
+    pub fn filter(&self, channel: NotificationChannel, now: DateTime<Utc>) -> Option<Filter> {
+        let (column, hours) = match channel {
+            NotificationChannel::Email => ("last_email_notification", self.email?),
+            NotificationChannel::Sms => ("last_sms_notification", self.sms?),
+            NotificationChannel::InApp => ("last_in_app_notification", self.in_app?),
+            NotificationChannel::Unspecified => return None,
+        };
+        let cutoff = now - Duration::hours(hours as _);
+
+        Some(Filter::or([
+            Filter::null(column),
+            Filter::time(column, None, Some(cutoff)),
+        ]))
+    }
+
+    /// Users reachable via the channels, and those of them not suppressed by the cap. A user is
+    /// reached via the first channel they have contact data for, and capped by that channel
+    /// only. None matches every user.
+    fn reach(
+        &self,
+        channels: &[NotificationChannel],
+        now: DateTime<Utc>,
+    ) -> (Option<Filter>, Option<Filter>) {
+        let mut reachable = Vec::new();
+        let mut uncapped = Vec::new();
+        let mut capped = false;
+        // contact columns of the channels before, which the user must not have
+        let mut before = Vec::new();
+        for &channel in channels {
+            let contact = contact_column(channel);
+            let mut reached: Vec<_> = before.iter().map(|c| Filter::null(*c)).collect();
+            reached.extend(contact.map(|c| Filter::not(Filter::null(c))));
+            reachable.push(all(reached.clone()));
+            if let Some(cap) = self.filter(channel, now) {
+                reached.push(cap);
+                capped = true;
+            }
+            uncapped.push(all(reached));
+            match contact {
+                Some(contact) => before.push(contact),
+                None => {
+                    // every user has an email, so the channels after it are never used
+                    reachable = vec![None];
+                    break;
+                }
+            }
+        }
+
+        let reachable = any(reachable);
+        let uncapped = if capped {
+            any(uncapped)
+        } else {
+            reachable.clone()
+        };
+        (reachable, uncapped)
+    }
+}
+
+/// contact column of the channel, None for email which every user has
+fn contact_column(channel: NotificationChannel) -> Option<&'static str> {
+    match channel {
+        NotificationChannel::Sms => Some("phone"),
+        NotificationChannel::InApp => Some("device_id"),
+        NotificationChannel::Email | NotificationChannel::Unspecified => None,
+    }
+}
+
+/// None is TRUE
+fn all(mut filters: Vec<Filter>) -> Option<Filter> {
+    match filters.len() {
+        0 => None,
+        1 => filters.pop(),
+        _ => Some(Filter::and(filters)),
+    }
+}
+
+/// None is TRUE
+fn any(filters: Vec<Option<Filter>>) -> Option<Filter> {
+    let mut filters = filters.into_iter().collect::<Option<Vec<_>>>()?;
+    match filters.len() {
+        0 => Some(Filter::or([])),
+        1 => filters.pop(),
+        _ => Some(Filter::or(filters)),
+    }
+}
+
+fn narrow(mut query: QueryRequest, filter: Option<Filter>) -> QueryRequest {
+    if let Some(filter) = filter {
+        query.filter = Some(match query.filter.take() {
+            Some(existing) => Filter::and([existing, filter]),
+            None => filter,
+        });
+    }
+    query
+}
+
+/// Users a campaign targets.
+pub(super) struct Segment {
+    pub query: QueryRequest,
+    /// users matched by the query and reachable via the channels
+    pub targeted: u64,
+    /// users excluded from the query by the frequency cap
+    pub suppressed: u64,
+}
+
+impl CrmService {
+    /// Narrow the query down to the users who could be notified via the channels, and count the
+    /// users targeted and suppressed by the frequency cap. The counts are taken before the
+    /// campaign is sent, so they may be slightly off if user stats change in the meantime.
+    pub(super) async fn apply_frequency_cap(
+        &self,
+        query: QueryRequest,
+        channels: &[NotificationChannel],
+    ) -> Result<Segment, Status> {
+        let (reachable, uncapped) = self.config.frequency_cap.reach(channels, Utc::now());
+        let mut user_stats = self.user_stats.clone();
+
+        let capped = reachable != uncapped;
+        let total_query = narrow(query.clone(), reachable);
+        let total = user_stats
+            .count(total_query.clone())
+            .await?
+            .into_inner()
+            .count;
+        if !capped {
+            return Ok(Segment {
+                query: total_query,
+                targeted: total,
+                suppressed: 0,
+            });
+        }
+
+        let query = narrow(query, uncapped);
+        let targeted = user_stats.count(query.clone()).await?.into_inner().count;
+
+        Ok(Segment {
+            query,
+            targeted,
+            suppressed: total.saturating_sub(targeted),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FrequencyCapConfig {
+        FrequencyCapConfig {
+            email: Some(72),
+            sms: None,
+            in_app: Some(24),
+        }
+    }
+
+    #[test]
+    fn email_should_reach_every_user() {
+        let now = Utc::now();
+        let (reachable, uncapped) = config().reach(&[NotificationChannel::Email], now);
+        assert_eq!(reachable, None);
+        assert_eq!(uncapped, config().filter(NotificationChannel::Email, now));
+
+        let (reachable, uncapped) =
+            FrequencyCapConfig::default().reach(&[NotificationChannel::Email], now);
+        assert_eq!((reachable, uncapped), (None, None));
+    }
+
+    #[test]
+    fn users_should_be_reached_via_their_first_channel() {
+        let now = Utc::now();
+        let channels = [
+            NotificationChannel::InApp,
+            NotificationChannel::Sms,
+            NotificationChannel::Email,
+            NotificationChannel::InApp,
+        ];
+        let (reachable, uncapped) = config().reach(&channels, now);
+
+        let in_app = Filter::not(Filter::null("device_id"));
+        let sms = Filter::and([
+            Filter::null("device_id"),
+            Filter::not(Filter::null("phone")),
+        ]);
+        assert_eq!(reachable, None);
+        assert_eq!(
+            uncapped,
+            Some(Filter::or([
+                Filter::and([
+                    in_app,
+                    config().filter(NotificationChannel::InApp, now).unwrap()
+                ]),
+                sms,
+                Filter::and([
+                    Filter::null("device_id"),
+                    Filter::null("phone"),
+                    config().filter(NotificationChannel::Email, now).unwrap()
+                ]),
+            ]))
+        );
+
+        // users without a phone number are not reachable, and sms is not capped
+        let (reachable, uncapped) = config().reach(&[NotificationChannel::Sms], now);
+        assert_eq!(reachable, Some(Filter::not(Filter::null("phone"))));
+        assert_eq!(uncapped, reachable);
+    }
+}
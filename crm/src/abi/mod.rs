@@ -1,57 +1,590 @@
-use crate::pb::{WelcomeRequest, WelcomeResponse};
-use crate::CrmService;
-use chrono::{Duration, Utc};
-use crm_metadata::pb::{Content, MaterializeRequest};
+#![allow(clippy::result_large_err)]
+
+use crate::pb::{
+    preview_request, Campaign, CampaignStatus, GetCampaignRequest, PreviewRequest, PreviewResponse,
+    RecallRequest, RecallResponse, RemindRequest, RemindResponse, Variant, WatchCampaignRequest,
+    WelcomeRequest, WelcomeResponse,
+};
+use crate::{CampaignStream, CrmService};
+use campaign::{CampaignProgress, Tracking};
+use chrono::{DateTime, Duration, Utc};
+use crm_metadata::pb::metadata_client::MetadataClient;
+use crm_metadata::pb::{Content, MaterializeRequest, RecommendRequest};
+use crm_metadata::{Recipient, Tpl, DEFAULT_TEMPLATE};
+use crm_send::pb::send_request::Msg;
 use crm_send::pb::SendRequest;
-use futures::StreamExt;
-use std::sync::Arc;
+use frequency_cap::Segment;
+use futures::{stream, Stream, StreamExt};
+use ledger::CampaignState;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
 use tonic::{Response, Status};
 use tracing::warn;
-use user_stat::pb::QueryRequest;
+use user_stat::pb::activity_event::Event;
+use user_stat::pb::{
+    ActivityEvent, Filter, Gender, NotificationChannel, QueryRequest, RecordNotificationRequest,
+    User,
+};
+use uuid::Uuid;
 
 pub mod auth;
+mod campaign;
+mod definition;
+mod frequency_cap;
+pub mod ledger;
+mod scheduler;
 
-impl CrmService {
-    pub async fn welcome(&self, req: WelcomeRequest) -> Result<Response<WelcomeResponse>, Status> {
-        let req_id = req.id;
-        let d1 = Utc::now() - Duration::days(req.interval as _);
+pub(crate) use campaign::CampaignTracker;
+pub(crate) use scheduler::Schedules;
+
+const CHANNEL_SIZE: usize = 1024;
+const DEFAULT_SAMPLE_SIZE: u32 = 10;
+const MAX_SAMPLE_SIZE: u32 = 100;
+
+/// What a campaign sends to each user.
+#[derive(Debug, Clone)]
+struct Message {
+    /// variants users are split into, never empty
+    variants: Vec<MessageVariant>,
+    /// channels in order of priority, never empty
+    channels: Vec<NotificationChannel>,
+}
+
+#[derive(Debug, Clone)]
+struct MessageVariant {
+    /// empty if the campaign is not split into variants
+    name: String,
+    weight: u32,
+    subject: String,
+    contents: Contents,
+    /// name of the template of the body
+    template: String,
+}
+
+#[derive(Debug, Clone)]
+enum Contents {
+    /// the same contents to every user
+    Ids(Vec<u32>),
+    /// each user the contents they have started but not finished
+    Unfinished,
+    /// each user this many contents recommended from what they have watched
+    Recommended(u32),
+}
+
+impl Message {
+    fn new(subject: &str, contents: Contents, channels: &[i32]) -> Result<Self, Status> {
+        let mut priority = Vec::new();
+        for &channel in channels {
+            match NotificationChannel::try_from(channel) {
+                Ok(NotificationChannel::Unspecified) | Err(_) => {
+                    return Err(Status::invalid_argument(format!(
+                        "Invalid channel: {channel}"
+                    )))
+                }
+                Ok(channel) if !priority.contains(&channel) => priority.push(channel),
+                Ok(_) => {}
+            }
+        }
+        if priority.is_empty() {
+            priority.push(NotificationChannel::Email);
+        }
+
+        let variant = MessageVariant {
+            name: String::new(),
+            weight: 1,
+            subject: subject.to_string(),
+            contents,
+            template: DEFAULT_TEMPLATE.to_string(),
+        };
+        Ok(Self {
+            variants: vec![variant],
+            channels: priority,
+        })
+    }
+
+    /// Render the message with the named template instead of the builtin one, which is kept if
+    /// the name is empty.
+    fn with_template(mut self, template: &str) -> Self {
+        if template.is_empty() {
+            return self;
+        }
+        for variant in &mut self.variants {
+            variant.template = template.to_string();
+        }
+        self
+    }
+
+    /// Split the message into the variants, which fall back to the subject, contents and
+    /// template of the message. Unfinished contents of users' own can't be replaced by content
+    /// ids, recommended ones can.
+    fn with_variants(mut self, variants: &[Variant]) -> Result<Self, Status> {
+        if variants.is_empty() {
+            return Ok(self);
+        }
+        let default = self.variants.remove(0);
+
+        let mut names = HashSet::new();
+        for v in variants {
+            if v.name.is_empty() || !names.insert(v.name.as_str()) {
+                return Err(Status::invalid_argument(format!(
+                    "Variant name must be unique and not empty: {:?}",
+                    v.name
+                )));
+            }
+            let contents = match (&default.contents, v.content_ids.is_empty()) {
+                (contents, true) => contents.clone(),
+                (Contents::Ids(_) | Contents::Recommended(_), false) => {
+                    Contents::Ids(v.content_ids.clone())
+                }
+                (Contents::Unfinished, false) => {
+                    return Err(Status::invalid_argument(format!(
+                        "Variant {} can't have content ids",
+                        v.name
+                    )))
+                }
+            };
+            let subject = if v.subject.is_empty() {
+                default.subject.clone()
+            } else {
+                v.subject.clone()
+            };
+            let template = if v.template.is_empty() {
+                default.template.clone()
+            } else {
+                v.template.clone()
+            };
+            self.variants.push(MessageVariant {
+                name: v.name.clone(),
+                weight: v.weight.max(1),
+                subject,
+                contents,
+                template,
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Index of the variant the user is assigned to, the same for the same email every time.
+    fn variant_of(&self, email: &str) -> usize {
+        if self.variants.len() == 1 {
+            return 0;
+        }
+        let total: u64 = self.variants.iter().map(|v| v.weight as u64).sum();
+        let mut point = fnv1a(email.as_bytes()) % total;
+        for (i, v) in self.variants.iter().enumerate() {
+            if point < v.weight as u64 {
+                return i;
+            }
+            point -= v.weight as u64;
+        }
+        unreachable!("point is less than the total weight")
+    }
+}
+
+/// A message rendered for a user.
+struct Rendered {
+    email: String,
+    /// name of the variant the user is assigned to, empty if there are no variants
+    variant: String,
+    req: SendRequest,
+}
+
+impl WelcomeRequest {
+    /// Users registered `interval` days ago get the given contents.
+    fn campaign(&self, now: DateTime<Utc>) -> Result<(QueryRequest, Message), Status> {
+        let d1 = now - Duration::days(self.interval as _);
         let d2 = d1 + Duration::days(1);
         let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let mut resp_user_stats = self.user_stats.clone().query(query).await?.into_inner();
+        let contents = Contents::Ids(self.content_ids.clone());
+        let message = Message::new("Welcome", contents, &self.channels)?;
+        let message = message.with_template(&self.template);
+        Ok((query, message.with_variants(&self.variants)?))
+    }
+}
 
-        let contents = self
-            .metadata
-            .clone()
-            .materialize(MaterializeRequest::new_with_ids(&req.content_ids))
-            .await?
-            .into_inner();
+impl RecallRequest {
+    /// Users last visited `last_visit_interval` days ago get the given contents, or contents
+    /// recommended for each of them.
+    fn campaign(&self, now: DateTime<Utc>) -> Result<(QueryRequest, Message), Status> {
+        let d1 = now - Duration::days(self.last_visit_interval as _);
+        let d2 = d1 + Duration::days(1);
+        let query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        let contents = match self.recommend {
+            0 => Contents::Ids(self.content_ids.clone()),
+            n => Contents::Recommended(n),
+        };
+        let message = Message::new("We miss you", contents, &self.channels)?;
+        let message = message.with_template(&self.template);
+        Ok((query, message.with_variants(&self.variants)?))
+    }
+}
+
+impl RemindRequest {
+    /// Users last visited `last_visit_interval` days ago get the contents they have not
+    /// finished.
+    fn campaign(&self, now: DateTime<Utc>) -> Result<(QueryRequest, Message), Status> {
+        let d1 = now - Duration::days(self.last_visit_interval as _);
+        let d2 = d1 + Duration::days(1);
+        let mut query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        query.filter = Some(Filter::not(Filter::null("started_but_not_finished")));
+        let message = Message::new("Continue watching", Contents::Unfinished, &self.channels)?;
+        let message = message.with_template(&self.template);
+        Ok((query, message.with_variants(&self.variants)?))
+    }
+}
+
+impl CrmService {
+    pub async fn welcome(&self, req: WelcomeRequest) -> Result<Response<WelcomeResponse>, Status> {
+        let (query, message) = req.campaign(Utc::now())?;
+        let fingerprint = fingerprint(
+            "welcome",
+            &WelcomeRequest {
+                id: String::new(),
+                ..req.clone()
+            },
+        );
+        let (id, suppressed) = self.launch(req.id, fingerprint, query, message).await?;
+
+        Ok(Response::new(WelcomeResponse { id, suppressed }))
+    }
+
+    pub async fn recall(&self, req: RecallRequest) -> Result<Response<RecallResponse>, Status> {
+        let (query, message) = req.campaign(Utc::now())?;
+        let fingerprint = fingerprint(
+            "recall",
+            &RecallRequest {
+                id: String::new(),
+                ..req.clone()
+            },
+        );
+        let (id, suppressed) = self.launch(req.id, fingerprint, query, message).await?;
+
+        Ok(Response::new(RecallResponse { id, suppressed }))
+    }
+
+    pub async fn remind(&self, req: RemindRequest) -> Result<Response<RemindResponse>, Status> {
+        let (query, message) = req.campaign(Utc::now())?;
+        let fingerprint = fingerprint(
+            "remind",
+            &RemindRequest {
+                id: String::new(),
+                ..req.clone()
+            },
+        );
+        let (id, suppressed) = self.launch(req.id, fingerprint, query, message).await?;
+
+        Ok(Response::new(RemindResponse { id, suppressed }))
+    }
 
-        let contents: Vec<Content> = contents
-            .filter_map(|v| async move { v.ok() })
+    /// Render a campaign the same way it would be sent, without touching the ledger or calling
+    /// the notification service.
+    pub async fn preview(&self, req: PreviewRequest) -> Result<Response<PreviewResponse>, Status> {
+        let now = Utc::now();
+        let (query, message) = match req.campaign {
+            Some(preview_request::Campaign::Welcome(req)) => req.campaign(now)?,
+            Some(preview_request::Campaign::Recall(req)) => req.campaign(now)?,
+            Some(preview_request::Campaign::Remind(req)) => req.campaign(now)?,
+            Some(preview_request::Campaign::Name(name)) => self.defined_campaign(&name)?,
+            None => return Err(Status::invalid_argument("campaign is required")),
+        };
+        let sample_size = match req.sample_size {
+            0 => DEFAULT_SAMPLE_SIZE,
+            n => n.min(MAX_SAMPLE_SIZE),
+        };
+
+        self.check_templates(&message)?;
+        let segment = self.apply_frequency_cap(query, &message.channels).await?;
+        let (rendered, rendering) = self
+            .render(segment.query, &message, HashSet::new(), None)
+            .await?;
+        let samples: Vec<_> = rendered
+            .take(sample_size as usize)
+            .map(|rendered| rendered.req)
             .collect()
             .await;
-        let contents = Arc::new(contents);
+        // the users ran out before the samples did, which could be because of an error
+        if samples.len() < sample_size as usize {
+            join_rendering(rendering).await?;
+        }
 
-        let (tx, rx) = mpsc::channel(1024);
+        Ok(Response::new(PreviewResponse {
+            targeted: segment.targeted,
+            suppressed: segment.suppressed,
+            samples,
+        }))
+    }
 
-        let sender = self.config.server.sender_email.clone();
+    pub async fn get_campaign(
+        &self,
+        req: GetCampaignRequest,
+    ) -> Result<Response<Campaign>, Status> {
+        let campaign = match self.campaigns.get(&req.id) {
+            Ok(campaign) => campaign,
+            Err(e) => self.finished_campaign(&req.id, e).await?,
+        };
+        Ok(Response::new(campaign))
+    }
+
+    pub async fn watch_campaign(
+        &self,
+        req: WatchCampaignRequest,
+    ) -> Result<Response<CampaignStream>, Status> {
+        let updates = self.watch(&req.id).await?;
+        Ok(Response::new(updates))
+    }
 
+    /// Progress of the campaign until it has finished, a campaign no longer tracked by this
+    /// process only has its final progress from the ledger.
+    async fn watch(&self, id: &str) -> Result<CampaignStream, Status> {
+        match self.campaigns.watch(id) {
+            Ok(updates) => Ok(Box::pin(updates)),
+            Err(e) => {
+                let campaign = self.finished_campaign(id, e).await?;
+                Ok(Box::pin(stream::once(async { Ok(campaign) })))
+            }
+        }
+    }
+
+    /// Final progress of the campaign from the ledger, or the error if it has not finished.
+    async fn finished_campaign(&self, id: &str, not_found: Status) -> Result<Campaign, Status> {
+        self.store.finished(id).await?.ok_or(not_found)
+    }
+
+    /// Start a campaign in the background, and return its id along with the number of users
+    /// suppressed by the frequency cap. A campaign which is running or has finished is not
+    /// started again, and a campaign which did not finish only sends to the remaining users.
+    /// The fingerprint identifies what the campaign is built from, an id can't be reused for a
+    /// campaign with a different fingerprint.
+    async fn launch(
+        &self,
+        id: String,
+        fingerprint: String,
+        query: QueryRequest,
+        message: Message,
+    ) -> Result<(String, u64), Status> {
+        self.check_templates(&message)?;
+        let id = if id.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            id
+        };
+        let progress = match self
+            .campaigns
+            .track(Campaign::new(&id, CampaignStatus::Pending), &fingerprint)?
+        {
+            Tracking::New(progress) => progress,
+            Tracking::Existing(campaign) => return Ok((id, campaign.suppressed)),
+        };
+
+        let prepared = self
+            .prepare(&id, &fingerprint, query, &message, &progress)
+            .await;
+        let (sent, segment) = match prepared {
+            Ok(Some(prepared)) => prepared,
+            Ok(None) => {
+                let suppressed = progress.get().suppressed;
+                progress.finish(&Ok(()));
+                self.campaigns.evict(&id);
+                return Ok((id, suppressed));
+            }
+            Err(e) => {
+                progress.finish(&Err(e.clone()));
+                return Err(e);
+            }
+        };
+        let suppressed = segment.suppressed;
+        progress.update(|c| {
+            c.targeted = segment.targeted;
+            c.suppressed = segment.suppressed;
+        });
+
+        let svc = self.clone();
+        let campaign_id = id.clone();
         tokio::spawn(async move {
-            while let Some(Ok(user)) = resp_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new("Welcome".to_string(), sender, &[user.email], &contents);
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
+            progress.update(|c| c.status = CampaignStatus::Running as _);
+            let id = campaign_id;
+            let ret = async {
+                let (reqs, rendering) = svc
+                    .render(segment.query, &message, sent, Some(progress.clone()))
+                    .await?;
+                svc.deliver(&id, reqs, &progress).await?;
+                // a segment cut short must be resumed, so it is not finished in the ledger
+                join_rendering(rendering).await?;
+                let mut campaign = progress.get();
+                campaign.status = CampaignStatus::Completed as _;
+                svc.store.finish(&id, &campaign).await
+            }
+            .await;
+            if let Err(e) = &ret {
+                warn!("Campaign {} failed: {:?}", id, e);
+            }
+            progress.finish(&ret);
+            // a failed campaign is kept for a while to tell why, it is not in the ledger
+            if ret.is_ok() {
+                svc.campaigns.evict(&id);
             }
         });
 
-        let reqs = ReceiverStream::new(rx);
+        Ok((id, suppressed))
+    }
+
+    fn check_templates(&self, message: &Message) -> Result<(), Status> {
+        match message
+            .variants
+            .iter()
+            .find(|v| !self.templates.contains(&v.template))
+        {
+            Some(v) => Err(Status::invalid_argument(format!(
+                "Template {} is not defined",
+                v.template
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Look the campaign up in the ledger and narrow its query down with the frequency cap.
+    /// Returns None if the campaign has finished before, with its final progress restored.
+    async fn prepare(
+        &self,
+        id: &str,
+        fingerprint: &str,
+        query: QueryRequest,
+        message: &Message,
+        progress: &CampaignProgress,
+    ) -> Result<Option<(HashSet<String>, Segment)>, Status> {
+        let sent = match self.store.start(id, fingerprint).await? {
+            CampaignState::Finished(campaign) => {
+                progress.update(|c| *c = campaign);
+                return Ok(None);
+            }
+            CampaignState::Resumed(sent) => sent,
+            CampaignState::New => HashSet::new(),
+        };
+        let segment = self.apply_frequency_cap(query, &message.channels).await?;
+
+        Ok(Some((sent, segment)))
+    }
+
+    /// Render the message of every user matched by the query, except those already sent to.
+    /// Each user gets the variant they are assigned to, via the first channel they have contact
+    /// data for. Users whose own contents could not be materialized, or whose message could not
+    /// be rendered, are counted as failed, and users with nothing to be sent are counted as
+    /// skipped. Rendering stops at the first error of the
+    /// users queried, which the returned handle resolves to once the stream has ended.
+    async fn render(
+        &self,
+        query: QueryRequest,
+        message: &Message,
+        sent: HashSet<String>,
+        progress: Option<CampaignProgress>,
+    ) -> Result<(ReceiverStream<Rendered>, JoinHandle<Result<(), Status>>), Status> {
+        let mut resp_user_stats = self.user_stats.clone().query(query).await?.into_inner();
+
+        // contents every user of the variant gets, None if each user gets their own
+        let mut shared = Vec::with_capacity(message.variants.len());
+        for variant in &message.variants {
+            let contents = match &variant.contents {
+                Contents::Ids(content_ids) => Some(Arc::new(
+                    materialize(self.metadata.clone(), content_ids).await?,
+                )),
+                Contents::Unfinished | Contents::Recommended(_) => None,
+            };
+            shared.push(contents);
+        }
+
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+
+        let svc = self.clone();
+        let message = message.clone();
+
+        let rendering = tokio::spawn(async move {
+            while let Some(user) = resp_user_stats.next().await {
+                let user = match user {
+                    Ok(user) => user,
+                    Err(e) => {
+                        warn!("Failed to query users: {:?}", e);
+                        return Err(e);
+                    }
+                };
+                let skip = || {
+                    if let Some(progress) = &progress {
+                        progress.skipped();
+                    }
+                };
+                if sent.contains(&user.email) {
+                    skip();
+                    continue;
+                }
+                let i = message.variant_of(&user.email);
+                let variant = &message.variants[i];
+                if matches!(variant.contents, Contents::Unfinished)
+                    && user.started_but_not_finished.is_empty()
+                {
+                    skip();
+                    continue;
+                }
+                let Some(channel) = channel_of(&user, &message.channels) else {
+                    skip();
+                    continue;
+                };
+
+                let contents = match &shared[i] {
+                    Some(contents) => contents.clone(),
+                    None => {
+                        let ret = match variant.contents {
+                            Contents::Recommended(n) => {
+                                recommend(svc.metadata.clone(), &user, n).await
+                            }
+                            _ => {
+                                let ids = &user.started_but_not_finished;
+                                materialize(svc.metadata.clone(), ids).await
+                            }
+                        };
+                        match ret {
+                            // nothing to notify the user about
+                            Ok(contents) if contents.is_empty() => {
+                                skip();
+                                continue;
+                            }
+                            Ok(contents) => Arc::new(contents),
+                            Err(e) => {
+                                warn!("Failed to materialize contents for {}: {:?}", user.email, e);
+                                if let Some(progress) = &progress {
+                                    progress.failed(channel, &variant.name, 1);
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                let req = match svc.message_to(&user, channel, variant, &contents) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        warn!("Failed to render message for {}: {:?}", user.email, e);
+                        if let Some(progress) = &progress {
+                            progress.failed(channel, &variant.name, 1);
+                        }
+                        continue;
+                    }
+                };
+                let rendered = Rendered {
+                    email: user.email,
+                    variant: variant.name.clone(),
+                    req,
+                };
+                if tx.send(rendered).await.is_err() {
+                    warn!("Receiver dropped, stop rendering messages");
+                    break;
+                }
+            }
+            Ok(())
+        });
 
         // NOTE: this is an alternative solution
         // let sender = self.config.server.sender_email.clone();
@@ -64,8 +597,285 @@ impl CrmService {
         //     }
         // });
 
-        self.notification.clone().send(reqs).await?;
+        Ok((ReceiverStream::new(rx), rendering))
+    }
+
+    /// Render the variant with the contents for the user via the channel, with the subject and
+    /// body in the language of the user.
+    fn message_to(
+        &self,
+        user: &User,
+        channel: NotificationChannel,
+        variant: &MessageVariant,
+        contents: &[Content],
+    ) -> Result<SendRequest, Status> {
+        let server = &self.config.server;
+        let recipient = recipient_of(user);
+        let tpl = Tpl::new(&self.templates, &variant.template, &recipient, contents);
+        match channel {
+            NotificationChannel::Sms => SendRequest::new_sms(
+                server.sender_phone.clone(),
+                std::slice::from_ref(&user.phone),
+                &tpl,
+            ),
+            NotificationChannel::InApp => SendRequest::new_in_app(
+                tpl.to_subject(&variant.subject)?,
+                user.device_id.clone(),
+                &tpl,
+            ),
+            NotificationChannel::Email | NotificationChannel::Unspecified => SendRequest::new(
+                tpl.to_subject(&variant.subject)?,
+                server.sender_email.clone(),
+                std::slice::from_ref(&user.email),
+                &tpl,
+            ),
+        }
+    }
+
+    /// Stream the rendered messages to the notification service and wait until all of them are
+    /// processed. Every delivered message is recorded in the campaign ledger along with its
+    /// variant, in its progress, and its notification time back to user stats. Messages without
+    /// a response are counted as failed.
+    async fn deliver(
+        &self,
+        campaign_id: &str,
+        reqs: impl Stream<Item = Rendered> + Send + 'static,
+        progress: &CampaignProgress,
+    ) -> Result<(), Status> {
+        // message id -> (user email, variant, channel) of the messages waiting for a response
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let reqs = {
+            let pending = pending.clone();
+            reqs.map(move |rendered| {
+                if let Some((message_id, channel)) = message_of(&rendered.req) {
+                    pending.lock().unwrap().insert(
+                        message_id.to_string(),
+                        (rendered.email, rendered.variant, channel),
+                    );
+                }
+                rendered.req
+            })
+        };
+        let mut resps = self.notification.clone().send(reqs).await?.into_inner();
+
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let mut user_stats = self.user_stats.clone();
+        let recording =
+            tokio::spawn(async move { user_stats.ingest(ReceiverStream::new(rx)).await });
+
+        while let Some(resp) = resps.next().await {
+            let resp = match resp {
+                Ok(resp) => resp,
+                Err(e) => {
+                    warn!("Failed to deliver message: {:?}", e);
+                    continue;
+                }
+            };
+            let Some((email, variant, channel)) = pending.lock().unwrap().remove(&resp.message_id)
+            else {
+                continue;
+            };
+            progress.sent(channel, &variant);
+            let variant = (!variant.is_empty()).then_some(variant.as_str());
+            if let Err(e) = self.store.record_sent(campaign_id, &email, variant).await {
+                warn!("Failed to record {} as sent: {:?}", email, e);
+            }
+            let event = Event::Notification(RecordNotificationRequest {
+                email,
+                channel: channel as _,
+                at: resp.timestamp,
+            });
+            if let Err(e) = tx.send(ActivityEvent { event: Some(event) }).await {
+                warn!("Failed to record notification: {:?}", e);
+            }
+        }
+        drop(tx);
+
+        for (_, (_, variant, channel)) in pending.lock().unwrap().drain() {
+            progress.failed(channel, &variant, 1);
+        }
+
+        // delivered messages must not fail the request because their stats are not recorded
+        match recording.await {
+            Ok(Ok(resp)) if resp.get_ref().rejected > 0 => {
+                warn!(
+                    "{} notifications were not recorded",
+                    resp.get_ref().rejected
+                )
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!("Failed to record notifications: {:?}", e),
+            Err(e) => warn!("Failed to record notifications: {:?}", e),
+        }
+
+        Ok(())
+    }
+}
+
+/// The first of the channels the user has contact data for.
+fn channel_of(user: &User, channels: &[NotificationChannel]) -> Option<NotificationChannel> {
+    channels.iter().copied().find(|channel| match channel {
+        NotificationChannel::Sms => !user.phone.is_empty(),
+        NotificationChannel::InApp => !user.device_id.is_empty(),
+        NotificationChannel::Email | NotificationChannel::Unspecified => true,
+    })
+}
+
+/// What templates know about the user.
+fn recipient_of(user: &User) -> Recipient {
+    let gender = match user.gender() {
+        Gender::Female => Some("female"),
+        Gender::Male => Some("male"),
+        Gender::Unknown => Some("unknown"),
+        Gender::Unspecified => None,
+    };
+    Recipient {
+        email: user.email.clone(),
+        name: user.name.clone(),
+        gender: gender.map(str::to_string),
+        recent_watched: user.recent_watched.clone(),
+        viewed_but_not_started: user.viewed_but_not_started.clone(),
+        started_but_not_finished: user.started_but_not_finished.clone(),
+        finished: user.finished.clone(),
+        locale: (!user.locale.is_empty()).then(|| user.locale.clone()),
+    }
+}
+
+/// message id and channel of the request
+fn message_of(req: &SendRequest) -> Option<(&str, NotificationChannel)> {
+    match req.msg.as_ref()? {
+        Msg::Email(email) => Some((&email.message_id, NotificationChannel::Email)),
+        Msg::Sms(sms) => Some((&sms.message_id, NotificationChannel::Sms)),
+        Msg::InApp(in_app) => Some((&in_app.message_id, NotificationChannel::InApp)),
+    }
+}
+
+/// Fingerprint of what a campaign of the kind is built from. Queries are not fingerprinted
+/// directly since their time ranges are relative to when the campaign is launched.
+fn fingerprint(kind: &str, definition: &impl prost::Message) -> String {
+    let hash = fnv1a(&[kind.as_bytes(), &definition.encode_to_vec()].concat());
+    format!("{kind}-{hash:016x}")
+}
+
+/// Fingerprint of a campaign defined in the config.
+fn config_fingerprint(kind: &str, definition: &impl serde::Serialize) -> Result<String, Status> {
+    let yaml = serde_yaml::to_string(definition).map_err(|e| {
+        warn!("Failed to serialize {} definition: {:?}", kind, e);
+        Status::internal(format!("Failed to fingerprint {kind}"))
+    })?;
+    let hash = fnv1a(&[kind.as_bytes(), yaml.as_bytes()].concat());
+    Ok(format!("{kind}-{hash:016x}"))
+}
+
+/// 64-bit FNV-1a hash, stable across processes and releases unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+async fn join_rendering(rendering: JoinHandle<Result<(), Status>>) -> Result<(), Status> {
+    rendering.await.map_err(|e| {
+        warn!("Failed to render messages: {:?}", e);
+        Status::internal("Failed to render messages")
+    })?
+}
+
+async fn recommend(
+    mut metadata: MetadataClient<Channel>,
+    user: &User,
+    limit: u32,
+) -> Result<Vec<Content>, Status> {
+    let req = RecommendRequest {
+        recent_watched: user.recent_watched.clone(),
+        finished: user.finished.clone(),
+        limit,
+        ..Default::default()
+    };
+    let ret = metadata.recommend(req).await?.into_inner();
+    Ok(ret.contents)
+}
+
+async fn materialize(
+    mut metadata: MetadataClient<Channel>,
+    ids: &[u32],
+) -> Result<Vec<Content>, Status> {
+    let contents = metadata
+        .materialize(MaterializeRequest::new_with_ids(ids))
+        .await?
+        .into_inner()
+        .filter_map(|v| async move {
+            match v.ok()?.into_result() {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    warn!("Failed to materialize content: {}", e.message());
+                    None
+                }
+            }
+        })
+        .collect()
+        .await;
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::VariantBuilder;
+    use anyhow::Result;
+
+    #[test]
+    fn variants_should_fall_back_to_the_message() -> Result<()> {
+        let message = Message::new("Welcome", Contents::Ids(vec![1]), &[])?.with_variants(&[
+            VariantBuilder::default().name("a").build()?,
+            VariantBuilder::default()
+                .name("b")
+                .subject("Hello")
+                .content_ids([2u32])
+                .build()?,
+        ])?;
+        let [a, b] = &message.variants[..] else {
+            panic!("message should have two variants");
+        };
+        assert_eq!((a.weight, a.subject.as_str()), (1, "Welcome"));
+        assert!(matches!(a.contents, Contents::Ids(ref ids) if ids == &[1]));
+        assert_eq!(b.subject, "Hello");
+        assert!(matches!(b.contents, Contents::Ids(ref ids) if ids == &[2]));
+
+        assert_eq!(a.template, DEFAULT_TEMPLATE);
+
+        let message = Message::new("Welcome", Contents::Unfinished, &[])?
+            .with_template("picks")
+            .with_variants(&[
+                VariantBuilder::default().name("a").build()?,
+                VariantBuilder::default()
+                    .name("b")
+                    .template("hot")
+                    .build()?,
+            ])?;
+        assert_eq!(message.variants[0].template, "picks");
+        assert_eq!(message.variants[1].template, "hot");
+        Ok(())
+    }
+
+    #[test]
+    fn users_should_be_assigned_to_variants_by_weight() -> Result<()> {
+        let message = Message::new("Welcome", Contents::Unfinished, &[])?;
+        assert_eq!(message.variant_of("alice@acme.org"), 0);
 
-        Ok(Response::new(WelcomeResponse { id: req_id }))
+        let message = message.with_variants(&[
+            VariantBuilder::default().name("a").build()?,
+            VariantBuilder::default().name("b").weight(3u32).build()?,
+        ])?;
+        let mut counts = [0; 2];
+        for i in 0..4000 {
+            let email = format!("user{i}@acme.org");
+            let variant = message.variant_of(&email);
+            assert_eq!(message.variant_of(&email), variant);
+            counts[variant] += 1;
+        }
+        assert!((900..1100).contains(&counts[0]), "{counts:?}");
+        Ok(())
     }
 }
@@ -0,0 +1,293 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use futures::StreamExt;
+use tokio::time::sleep;
+use tonic::{Response, Status};
+use tracing::{info, warn};
+use user_stat::pb::QueryRequest;
+
+use super::definition::channel_ids;
+use super::{config_fingerprint, Message};
+use crate::config::{ScheduleConfig, ScheduledCampaign};
+use crate::pb::{
+    CampaignStatus, RecallRequest, RemindRequest, RunCampaignResponse, RunScheduleRequest,
+    WelcomeRequest,
+};
+use crate::{AppConfig, CrmService};
+
+/// Campaigns run on a cron schedule, from the schedules and the defined campaigns with a
+/// schedule in the config.
+pub(crate) struct Schedules(HashMap<String, Scheduled>);
+
+struct Scheduled {
+    cron: Schedule,
+    run: ScheduledRun,
+}
+
+enum ScheduledRun {
+    Builtin(ScheduleConfig),
+    /// the defined campaign of the same name
+    Defined,
+}
+
+impl Schedules {
+    pub fn try_new(config: &AppConfig) -> Result<Self> {
+        let mut schedules = HashMap::new();
+        for (name, schedule) in &config.schedules {
+            let run = ScheduledRun::Builtin(schedule.clone());
+            schedules.insert(name.clone(), Scheduled::try_new(name, &schedule.cron, run)?);
+        }
+        for (name, campaign) in &config.campaigns {
+            let Some(cron) = &campaign.schedule else {
+                continue;
+            };
+            if schedules.contains_key(name) {
+                bail!("Schedule {name} is defined as both a schedule and a campaign");
+            }
+            let scheduled = Scheduled::try_new(name, cron, ScheduledRun::Defined)?;
+            schedules.insert(name.clone(), scheduled);
+        }
+        Ok(Self(schedules))
+    }
+}
+
+impl Scheduled {
+    fn try_new(name: &str, cron: &str, run: ScheduledRun) -> Result<Self> {
+        let Ok(cron) = Schedule::from_str(cron) else {
+            bail!("Invalid cron expression of schedule {name}: {cron}");
+        };
+        Ok(Self { cron, run })
+    }
+}
+
+impl ScheduleConfig {
+    fn campaign(&self, now: DateTime<Utc>) -> Result<(QueryRequest, Message), Status> {
+        let channels = channel_ids(&self.channels);
+        let template = self.template.clone().unwrap_or_default();
+        match self.run.clone() {
+            ScheduledCampaign::Welcome {
+                interval,
+                content_ids,
+            } => WelcomeRequest {
+                interval,
+                content_ids,
+                channels,
+                template,
+                ..Default::default()
+            }
+            .campaign(now),
+            ScheduledCampaign::Recall {
+                last_visit_interval,
+                content_ids,
+                recommend,
+            } => RecallRequest {
+                last_visit_interval,
+                content_ids,
+                recommend,
+                channels,
+                template,
+                ..Default::default()
+            }
+            .campaign(now),
+            ScheduledCampaign::Remind {
+                last_visit_interval,
+            } => RemindRequest {
+                last_visit_interval,
+                channels,
+                template,
+                ..Default::default()
+            }
+            .campaign(now),
+        }
+    }
+}
+
+impl CrmService {
+    /// Run every schedule in the background. A schedule which has missed runs while the server
+    /// was down runs once right away.
+    pub fn start_scheduler(&self) {
+        for name in self.schedules.0.keys() {
+            info!("Scheduling {}", name);
+            tokio::spawn(self.clone().run_on_schedule(name.clone()));
+        }
+    }
+
+    pub async fn run_schedule(
+        &self,
+        req: RunScheduleRequest,
+    ) -> Result<Response<RunCampaignResponse>, Status> {
+        let (query, message) = self.scheduled_campaign(&req.name, Utc::now())?;
+        let fingerprint = self.scheduled_fingerprint(&req.name)?;
+        let (id, suppressed) = self.launch(req.id, fingerprint, query, message).await?;
+
+        Ok(Response::new(RunCampaignResponse { id, suppressed }))
+    }
+
+    /// Run the schedules due at the given time, those which have missed a run since their last
+    /// one, and wait until they have finished. Missed runs are collapsed into the latest of them,
+    /// and schedules which have never run wait for their upcoming time instead. Returns the ids
+    /// of the campaigns run.
+    pub async fn run_due_schedules(&self, now: DateTime<Utc>) -> Result<Vec<String>, Status> {
+        let mut names: Vec<_> = self.schedules.0.keys().collect();
+        names.sort();
+        let mut ids = Vec::new();
+        for name in names {
+            let last = self.store.last_run(name).await?;
+            match next_run(&self.schedules.0[name].cron, last, now) {
+                Some(at) if at <= now => ids.push(self.run_at(name, at).await?),
+                _ => {}
+            }
+        }
+        Ok(ids)
+    }
+
+    fn scheduled_campaign(
+        &self,
+        name: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(QueryRequest, Message), Status> {
+        let Some(scheduled) = self.schedules.0.get(name) else {
+            return Err(Status::not_found(format!("Schedule {name} is not defined")));
+        };
+        match &scheduled.run {
+            ScheduledRun::Builtin(schedule) => schedule.campaign(at),
+            ScheduledRun::Defined => self.config.campaigns[name].campaign(at),
+        }
+    }
+
+    fn scheduled_fingerprint(&self, name: &str) -> Result<String, Status> {
+        match &self.schedules.0[name].run {
+            ScheduledRun::Builtin(schedule) => {
+                config_fingerprint(&format!("schedule {name}"), schedule)
+            }
+            ScheduledRun::Defined => self.config.campaigns[name].fingerprint(name),
+        }
+    }
+
+    async fn run_on_schedule(self, name: String) {
+        let cron = &self.schedules.0[&name].cron;
+        let mut last = match self.store.last_run(&name).await {
+            Ok(last) => last,
+            Err(e) => {
+                warn!("Failed to get the last run of {}: {:?}", name, e);
+                None
+            }
+        };
+
+        while let Some(at) = next_run(cron, last, Utc::now()) {
+            sleep((at - Utc::now()).to_std().unwrap_or_default()).await;
+            if let Err(e) = self.run_at(&name, at).await {
+                warn!("Scheduled run of {} at {} failed: {:?}", name, at, e);
+            }
+            last = Some(at);
+        }
+    }
+
+    /// Run the schedule as scheduled at the given time, and record the run once it has
+    /// finished. Returns the id of the campaign.
+    async fn run_at(&self, name: &str, at: DateTime<Utc>) -> Result<String, Status> {
+        // the id is derived from the scheduled time, so a run replayed after a restart only
+        // sends to the remaining users
+        let id = format!("{name}-{}", at.format("%Y%m%dT%H%M%SZ"));
+        self.run_scheduled(name, &id, at).await?;
+        self.store.record_run(name, at).await?;
+        Ok(id)
+    }
+
+    /// Launch the campaign as scheduled at the given time, and wait until it has finished.
+    async fn run_scheduled(&self, name: &str, id: &str, at: DateTime<Utc>) -> Result<(), Status> {
+        let (query, message) = self.scheduled_campaign(name, at)?;
+        let fingerprint = self.scheduled_fingerprint(name)?;
+        let (id, _) = self
+            .launch(id.to_string(), fingerprint, query, message)
+            .await?;
+
+        let mut updates = self.watch(&id).await?;
+        let mut campaign = None;
+        while let Some(update) = updates.next().await {
+            campaign = Some(update?);
+        }
+        match campaign {
+            Some(c) if c.status == CampaignStatus::Failed as i32 => Err(Status::internal(c.error)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// When to run next after the last run. Runs missed since the last run are collapsed into the
+/// latest of them, which is due already.
+fn next_run(
+    cron: &Schedule,
+    last: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let Some(last) = last else {
+        return cron.after(&now).next();
+    };
+    let mut upcoming = cron.after(&last);
+    let next = upcoming.next()?;
+    if next > now {
+        return Some(next);
+    }
+    Some(upcoming.take_while(|at| *at <= now).last().unwrap_or(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn next_run_should_wait_for_the_upcoming_time() {
+        let cron = Schedule::from_str("0 0 9 * * *").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 11, 5, 8, 0, 0).unwrap();
+        let nine = Utc.with_ymd_and_hms(2024, 11, 5, 9, 0, 0).unwrap();
+
+        assert_eq!(next_run(&cron, None, now), Some(nine));
+        let last = nine - Duration::days(1);
+        assert_eq!(next_run(&cron, Some(last), now), Some(nine));
+    }
+
+    #[test]
+    fn next_run_should_collapse_missed_runs() {
+        let cron = Schedule::from_str("0 0 9 * * *").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 11, 5, 10, 0, 0).unwrap();
+        let nine = Utc.with_ymd_and_hms(2024, 11, 5, 9, 0, 0).unwrap();
+
+        // never run before, wait for tomorrow instead of running right away
+        assert_eq!(next_run(&cron, None, now), Some(nine + Duration::days(1)));
+        // down for three days, run once for today
+        let last = nine - Duration::days(3);
+        assert_eq!(next_run(&cron, Some(last), now), Some(nine));
+        // already run today
+        assert_eq!(
+            next_run(&cron, Some(nine), now),
+            Some(nine + Duration::days(1))
+        );
+    }
+
+    #[test]
+    fn schedules_should_load() -> Result<()> {
+        let config = AppConfig::try_load()?;
+        let schedules = Schedules::try_new(&config)?;
+        let ScheduledRun::Builtin(schedule) = &schedules.0["daily_welcome"].run else {
+            panic!("daily welcome should be a builtin campaign");
+        };
+        assert!(matches!(
+            schedule.run,
+            ScheduledCampaign::Welcome { interval: 7, .. }
+        ));
+        assert!(matches!(
+            schedules.0["weekly_picks"].run,
+            ScheduledRun::Defined
+        ));
+        assert!(!schedules.0.contains_key("continue_watching"));
+        Ok(())
+    }
+}
@@ -20,6 +20,7 @@ async fn main() -> Result<()> {
     let token = include_str!("../../fixtures/token").trim();
     let token: MetadataValue<_> = format!("Bearer {token}").parse()?;
 
+    #[allow(clippy::result_large_err)]
     let mut client = CrmClient::with_interceptor(channel, move |mut req: Request<()>| {
         req.metadata_mut().insert("authorization", token.clone());
         Ok(req)
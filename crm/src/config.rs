@@ -1,5 +1,7 @@
 use anyhow::{bail, Result};
+use crm_metadata::{TemplateSource, Templates};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 
@@ -7,15 +9,30 @@ use std::fs::File;
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub frequency_cap: FrequencyCapConfig,
+    /// campaigns which can be run by name
+    #[serde(default)]
+    pub campaigns: HashMap<String, CampaignConfig>,
+    /// welcome, recall and remind campaigns run on a schedule, keyed by name
+    #[serde(default)]
+    pub schedules: HashMap<String, ScheduleConfig>,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: String,
     pub sender_email: String,
+    /// phone number sms are sent from
+    #[serde(default)]
+    pub sender_phone: String,
     pub metadata: String,
     pub user_stats: String,
     pub notification: String,
+    /// postgres database of the campaign ledger, the ledger is kept in memory if not set
+    pub db_url: Option<String>,
     pub tls: Option<TlsConfig>,
 }
 
@@ -30,6 +47,145 @@ pub struct AuthConfig {
     pub pk: String,
 }
 
+/// Named templates of message bodies, on top of the builtin `default` template. Templates of
+/// the same name in the config replace those in the directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TemplatesConfig {
+    /// directory of `<name>.html`, `<name>.txt` and `<name>.sms` files
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub inline: HashMap<String, TemplateSource>,
+}
+
+/// Minimum hours between two notifications of the same channel to a user, no cap if not set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrequencyCapConfig {
+    pub email: Option<u32>,
+    pub sms: Option<u32>,
+    pub in_app: Option<u32>,
+}
+
+/// A campaign defined in the config, run by the RunCampaign rpc.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignConfig {
+    pub subject: String,
+    /// users matching all of the filters are notified
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub segment: Vec<SegmentFilter>,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub contents: ContentSelection,
+    /// channels in order of priority, email only if empty
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+    /// name of the template of the message body, the builtin template if not set
+    pub template: Option<String>,
+    /// cron expression of when to run the campaign, only run on request if not set
+    pub schedule: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// cron expression with seconds in UTC, say `0 0 9 * * *` is daily at 9:00
+    pub cron: String,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub run: ScheduledCampaign,
+    /// channels in order of priority, email only if empty
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+    /// name of the template of the message, the builtin template if not set
+    pub template: Option<String>,
+}
+
+/// The parameters of the welcome, recall and remind rpcs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledCampaign {
+    Welcome {
+        interval: u32,
+        content_ids: Vec<u32>,
+    },
+    Recall {
+        last_visit_interval: u32,
+        #[serde(default)]
+        content_ids: Vec<u32>,
+        /// number of contents recommended to each user instead of the content ids
+        #[serde(default)]
+        recommend: u32,
+    },
+    Remind {
+        last_visit_interval: u32,
+    },
+}
+
+/// A filter over the columns of user stats. Days are counted back from when the campaign runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentFilter {
+    /// the timestamp column is between `from_days_ago` and `to_days_ago`, unbounded if not set
+    Between {
+        column: String,
+        from_days_ago: Option<u32>,
+        to_days_ago: Option<u32>,
+    },
+    /// the column is NULL, or empty for id columns
+    Null(String),
+    NotNull(String),
+    Ids {
+        column: String,
+        op: IdOperatorConfig,
+        ids: Vec<u32>,
+    },
+    Gender(GenderConfig),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdOperatorConfig {
+    Contains,
+    Overlaps,
+    ContainedBy,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenderConfig {
+    Female,
+    Male,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentSelection {
+    /// the same contents to every user
+    Ids(Vec<u32>),
+    /// each user the contents they have started but not finished
+    Unfinished,
+    /// each user this many contents recommended from what they have watched
+    Recommended(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelConfig {
+    Email,
+    Sms,
+    InApp,
+}
+
+impl TemplatesConfig {
+    pub fn load(&self) -> Result<Templates> {
+        let mut templates = Templates::default();
+        if let Some(dir) = &self.dir {
+            templates.load_dir(dir)?;
+        }
+        for (name, source) in &self.inline {
+            templates.add_source(name, source)?;
+        }
+        Ok(templates)
+    }
+}
+
 impl AppConfig {
     pub fn try_load() -> Result<Self> {
         let config = match (
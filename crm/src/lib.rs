@@ -3,27 +3,46 @@ mod config;
 
 pub mod pb;
 
-pub use config::AppConfig;
+pub use abi::ledger::{CampaignState, CampaignStore, MemoryCampaignStore, PgCampaignStore};
+pub use config::{AppConfig, ScheduleConfig, ScheduledCampaign};
 
-use crate::abi::auth;
+use crate::abi::{auth, CampaignTracker, Schedules};
 use crate::pb::crm_server::{Crm, CrmServer};
 use crate::pb::{
-    RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest, WelcomeResponse,
+    Campaign, GetCampaignRequest, PreviewRequest, PreviewResponse, RecallRequest, RecallResponse,
+    RemindRequest, RemindResponse, RunCampaignRequest, RunCampaignResponse, RunScheduleRequest,
+    WatchCampaignRequest, WelcomeRequest, WelcomeResponse,
 };
 use anyhow::Result;
 use crm_metadata::pb::metadata_client::MetadataClient;
+use crm_metadata::Templates;
 use crm_send::pb::notification_client::NotificationClient;
+use futures::Stream;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
 use tonic::codegen::InterceptedService;
 use tonic::transport::Channel;
 use tonic::{async_trait, Request, Response, Status};
-use tracing::info;
+use tracing::{info, warn};
 use user_stat::pb::user_stats_client::UserStatsClient;
 
+type CampaignStream = Pin<Box<dyn Stream<Item = Result<Campaign, Status>> + Send>>;
+
+#[derive(Clone)]
 pub struct CrmService {
+    inner: Arc<CrmServiceInner>,
+}
+
+pub struct CrmServiceInner {
     config: AppConfig,
     user_stats: UserStatsClient<Channel>,
     notification: NotificationClient<Channel>,
     metadata: MetadataClient<Channel>,
+    store: Arc<dyn CampaignStore>,
+    campaigns: CampaignTracker,
+    schedules: Schedules,
+    templates: Templates,
 }
 
 #[async_trait]
@@ -39,30 +58,101 @@ impl Crm for CrmService {
 
     async fn recall(
         &self,
-        _request: Request<RecallRequest>,
+        request: Request<RecallRequest>,
     ) -> std::result::Result<Response<RecallResponse>, Status> {
-        todo!()
+        let user: &auth::User = request.extensions().get().expect("");
+        info!("User: {:?}", user);
+        self.recall(request.into_inner()).await
     }
 
     async fn remind(
         &self,
-        _request: Request<RemindRequest>,
+        request: Request<RemindRequest>,
     ) -> std::result::Result<Response<RemindResponse>, Status> {
-        todo!()
+        let user: &auth::User = request.extensions().get().expect("");
+        info!("User: {:?}", user);
+        self.remind(request.into_inner()).await
+    }
+
+    async fn run_campaign(
+        &self,
+        request: Request<RunCampaignRequest>,
+    ) -> std::result::Result<Response<RunCampaignResponse>, Status> {
+        let user: &auth::User = request.extensions().get().expect("");
+        info!("User: {:?}", user);
+        self.run_campaign(request.into_inner()).await
+    }
+
+    async fn run_schedule(
+        &self,
+        request: Request<RunScheduleRequest>,
+    ) -> std::result::Result<Response<RunCampaignResponse>, Status> {
+        let user: &auth::User = request.extensions().get().expect("");
+        info!("User: {:?}", user);
+        self.run_schedule(request.into_inner()).await
+    }
+
+    async fn get_campaign(
+        &self,
+        request: Request<GetCampaignRequest>,
+    ) -> std::result::Result<Response<Campaign>, Status> {
+        self.get_campaign(request.into_inner()).await
+    }
+
+    type WatchCampaignStream = CampaignStream;
+
+    async fn watch_campaign(
+        &self,
+        request: Request<WatchCampaignRequest>,
+    ) -> std::result::Result<Response<Self::WatchCampaignStream>, Status> {
+        self.watch_campaign(request.into_inner()).await
+    }
+
+    async fn preview(
+        &self,
+        request: Request<PreviewRequest>,
+    ) -> std::result::Result<Response<PreviewResponse>, Status> {
+        let user: &auth::User = request.extensions().get().expect("");
+        info!("User: {:?}", user);
+        self.preview(request.into_inner()).await
     }
 }
 
 impl CrmService {
     pub async fn try_new(config: AppConfig) -> Result<Self> {
+        let store: Arc<dyn CampaignStore> = match &config.server.db_url {
+            Some(db_url) => Arc::new(PgCampaignStore::try_new(db_url).await?),
+            None => {
+                warn!("No db_url is configured, the campaign ledger does not survive restarts");
+                Arc::new(MemoryCampaignStore::default())
+            }
+        };
+        Self::try_new_with_store(config, store).await
+    }
+
+    pub async fn try_new_with_store(
+        config: AppConfig,
+        store: Arc<dyn CampaignStore>,
+    ) -> Result<Self> {
         let user_stats = UserStatsClient::connect(config.server.user_stats.clone()).await?;
         let notification = NotificationClient::connect(config.server.notification.clone()).await?;
         let metadata = MetadataClient::connect(config.server.metadata.clone()).await?;
 
-        Ok(Self {
+        let schedules = Schedules::try_new(&config)?;
+        let templates = config.templates.load()?;
+
+        let inner = CrmServiceInner {
             config,
             user_stats,
             notification,
             metadata,
+            store,
+            campaigns: CampaignTracker::default(),
+            schedules,
+            templates,
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
         })
     }
 
@@ -71,3 +161,11 @@ impl CrmService {
         Ok(CrmServer::with_interceptor(self, dk))
     }
 }
+
+impl Deref for CrmService {
+    type Target = CrmServiceInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
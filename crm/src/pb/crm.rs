@@ -1,4 +1,29 @@
 // This file is @generated by prost-build.
+/// a variant of the message of a campaign
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Variant {
+    /// unique in the campaign, recorded with each message sent
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub name: ::prost::alloc::string::String,
+    /// relative share of the users, 1 if not set
+    #[prost(uint32, tag = "2")]
+    pub weight: u32,
+    /// subject of the campaign if empty
+    #[prost(string, tag = "3")]
+    #[builder(setter(into))]
+    pub subject: ::prost::alloc::string::String,
+    /// content ids of the campaign if empty
+    #[prost(uint32, repeated, tag = "4")]
+    #[builder(setter(each(name = "content_id", into)))]
+    pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// name of the template of the message body, the template of the campaign if empty
+    #[prost(string, tag = "5")]
+    #[builder(setter(into))]
+    pub template: ::prost::alloc::string::String,
+}
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -11,11 +36,31 @@ pub struct WelcomeRequest {
     #[prost(uint32, repeated, tag = "3")]
     #[builder(setter(each(name = "content_id", into)))]
     pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// channels in order of priority, each user is notified via the first one they have contact
+    /// data for, email only if empty
+    #[prost(
+        enumeration = "::user_stat::pb::NotificationChannel",
+        repeated,
+        tag = "4"
+    )]
+    #[builder(setter(each(name = "channel")))]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// variants to test against each other, users are assigned to one of them by their email
+    #[prost(message, repeated, tag = "5")]
+    #[builder(setter(each(name = "variant")))]
+    pub variants: ::prost::alloc::vec::Vec<Variant>,
+    /// name of the template of the message, the builtin template if empty
+    #[prost(string, tag = "6")]
+    pub template: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct WelcomeResponse {
+    /// id of the campaign, which is the request id if set
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// matched users who are not notified because of the frequency cap
+    #[prost(uint64, tag = "2")]
+    pub suppressed: u64,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -26,12 +71,37 @@ pub struct RecallRequest {
     #[prost(uint32, tag = "2")]
     pub last_visit_interval: u32,
     #[prost(uint32, repeated, tag = "3")]
+    #[builder(setter(each(name = "content_id", into)))]
     pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// channels in order of priority, each user is notified via the first one they have contact
+    /// data for, email only if empty
+    #[prost(
+        enumeration = "::user_stat::pb::NotificationChannel",
+        repeated,
+        tag = "4"
+    )]
+    #[builder(setter(each(name = "channel")))]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// variants to test against each other, users are assigned to one of them by their email
+    #[prost(message, repeated, tag = "5")]
+    #[builder(setter(each(name = "variant")))]
+    pub variants: ::prost::alloc::vec::Vec<Variant>,
+    /// name of the template of the message, the builtin template if empty
+    #[prost(string, tag = "6")]
+    pub template: ::prost::alloc::string::String,
+    /// if set, each user gets this many contents recommended from what they have watched instead
+    /// of content_ids
+    #[prost(uint32, tag = "7")]
+    pub recommend: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RecallResponse {
+    /// id of the campaign, which is the request id if set
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// matched users who are not notified because of the frequency cap
+    #[prost(uint64, tag = "2")]
+    pub suppressed: u64,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -41,12 +111,195 @@ pub struct RemindRequest {
     pub id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub last_visit_interval: u32,
+    /// channels in order of priority, each user is notified via the first one they have contact
+    /// data for, email only if empty
+    #[prost(
+        enumeration = "::user_stat::pb::NotificationChannel",
+        repeated,
+        tag = "3"
+    )]
+    #[builder(setter(each(name = "channel")))]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// variants of the subject, their content ids must be empty as each user gets their own
+    #[prost(message, repeated, tag = "4")]
+    #[builder(setter(each(name = "variant")))]
+    pub variants: ::prost::alloc::vec::Vec<Variant>,
+    /// name of the template of the message, the builtin template if empty
+    #[prost(string, tag = "5")]
+    pub template: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RemindResponse {
+    /// id of the campaign, which is the request id if set
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// matched users who are not notified because of the frequency cap
+    #[prost(uint64, tag = "2")]
+    pub suppressed: u64,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunCampaignRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub id: ::prost::alloc::string::String,
+    /// name of a campaign defined in the config
+    #[prost(string, tag = "2")]
+    #[builder(setter(into))]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunCampaignResponse {
+    /// id of the campaign, which is the request id if set
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// matched users who are not notified because of the frequency cap
+    #[prost(uint64, tag = "2")]
+    pub suppressed: u64,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunScheduleRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub id: ::prost::alloc::string::String,
+    /// name of a schedule, or of a defined campaign with a schedule
+    #[prost(string, tag = "2")]
+    #[builder(setter(into))]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ChannelStats {
+    #[prost(uint64, tag = "1")]
+    pub sent: u64,
+    #[prost(uint64, tag = "2")]
+    pub failed: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Campaign {
+    /// id of the request which started the campaign
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(enumeration = "CampaignStatus", tag = "2")]
+    pub status: i32,
+    /// users matched by the segment, reachable via the channels and not suppressed by the
+    /// frequency cap
+    #[prost(uint64, tag = "3")]
+    pub targeted: u64,
+    /// messages delivered by this run, a resumed campaign does not count earlier runs
+    #[prost(uint64, tag = "4")]
+    pub sent: u64,
+    /// messages which could not be built or delivered
+    #[prost(uint64, tag = "5")]
+    pub failed: u64,
+    #[prost(uint64, tag = "6")]
+    pub suppressed: u64,
+    /// sent and failed of each channel, keyed by email, sms or in_app
+    #[prost(map = "string, message", tag = "7")]
+    pub channels: ::std::collections::HashMap<::prost::alloc::string::String, ChannelStats>,
+    /// why the campaign has failed
+    #[prost(string, tag = "8")]
+    pub error: ::prost::alloc::string::String,
+    /// sent and failed of each variant, keyed by the variant name
+    #[prost(map = "string, message", tag = "9")]
+    pub variants: ::std::collections::HashMap<::prost::alloc::string::String, ChannelStats>,
+    /// targeted users who are not notified: sent by an earlier run, not reachable via any of the
+    /// channels, or with no contents to be notified about. sent + failed + skipped adds up to
+    /// targeted once the campaign has finished
+    #[prost(uint64, tag = "10")]
+    pub skipped: u64,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetCampaignRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub id: ::prost::alloc::string::String,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchCampaignRequest {
     #[prost(string, tag = "1")]
+    #[builder(setter(into))]
     pub id: ::prost::alloc::string::String,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PreviewRequest {
+    /// number of rendered messages to return, defaults to 10 and at most 100
+    #[prost(uint32, tag = "4")]
+    pub sample_size: u32,
+    /// the campaign to preview, its id is ignored
+    #[prost(oneof = "preview_request::Campaign", tags = "1, 2, 3, 5")]
+    pub campaign: ::core::option::Option<preview_request::Campaign>,
+}
+/// Nested message and enum types in `PreviewRequest`.
+pub mod preview_request {
+    /// the campaign to preview, its id is ignored
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Campaign {
+        #[prost(message, tag = "1")]
+        Welcome(super::WelcomeRequest),
+        #[prost(message, tag = "2")]
+        Recall(super::RecallRequest),
+        #[prost(message, tag = "3")]
+        Remind(super::RemindRequest),
+        /// name of a campaign defined in the config
+        #[prost(string, tag = "5")]
+        Name(::prost::alloc::string::String),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PreviewResponse {
+    /// users who would be notified
+    #[prost(uint64, tag = "1")]
+    pub targeted: u64,
+    /// matched users who would not be notified because of the frequency cap
+    #[prost(uint64, tag = "2")]
+    pub suppressed: u64,
+    /// the first rendered messages, none of them is sent
+    #[prost(message, repeated, tag = "3")]
+    pub samples: ::prost::alloc::vec::Vec<::crm_send::pb::SendRequest>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum CampaignStatus {
+    Unspecified = 0,
+    /// accepted, waiting to be run
+    Pending = 1,
+    Running = 2,
+    Completed = 3,
+    Failed = 4,
+}
+impl CampaignStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "CAMPAIGN_STATUS_UNSPECIFIED",
+            Self::Pending => "CAMPAIGN_STATUS_PENDING",
+            Self::Running => "CAMPAIGN_STATUS_RUNNING",
+            Self::Completed => "CAMPAIGN_STATUS_COMPLETED",
+            Self::Failed => "CAMPAIGN_STATUS_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CAMPAIGN_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "CAMPAIGN_STATUS_PENDING" => Some(Self::Pending),
+            "CAMPAIGN_STATUS_RUNNING" => Some(Self::Running),
+            "CAMPAIGN_STATUS_COMPLETED" => Some(Self::Completed),
+            "CAMPAIGN_STATUS_FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod crm_client {
     #![allow(
@@ -179,6 +432,86 @@ pub mod crm_client {
                 .insert(GrpcMethod::new("crm.Crm", "Remind"));
             self.inner.unary(req, path, codec).await
         }
+        /// run a campaign defined in the config by its name
+        pub async fn run_campaign(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RunCampaignRequest>,
+        ) -> std::result::Result<tonic::Response<super::RunCampaignResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/RunCampaign");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "RunCampaign"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// run a scheduled campaign now, without waiting for or affecting its schedule
+        pub async fn run_schedule(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RunScheduleRequest>,
+        ) -> std::result::Result<tonic::Response<super::RunCampaignResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/RunSchedule");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "RunSchedule"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// campaigns run in the background, get the current progress of one
+        pub async fn get_campaign(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetCampaignRequest>,
+        ) -> std::result::Result<tonic::Response<super::Campaign>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/GetCampaign");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "GetCampaign"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// stream the progress of a campaign until it has completed or failed
+        pub async fn watch_campaign(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WatchCampaignRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::Campaign>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/WatchCampaign");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "WatchCampaign"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// render a campaign without sending anything, to check its audience and messages
+        pub async fn preview(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PreviewRequest>,
+        ) -> std::result::Result<tonic::Response<super::PreviewResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/Preview");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "Preview"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -209,6 +542,36 @@ pub mod crm_server {
             &self,
             request: tonic::Request<super::RemindRequest>,
         ) -> std::result::Result<tonic::Response<super::RemindResponse>, tonic::Status>;
+        /// run a campaign defined in the config by its name
+        async fn run_campaign(
+            &self,
+            request: tonic::Request<super::RunCampaignRequest>,
+        ) -> std::result::Result<tonic::Response<super::RunCampaignResponse>, tonic::Status>;
+        /// run a scheduled campaign now, without waiting for or affecting its schedule
+        async fn run_schedule(
+            &self,
+            request: tonic::Request<super::RunScheduleRequest>,
+        ) -> std::result::Result<tonic::Response<super::RunCampaignResponse>, tonic::Status>;
+        /// campaigns run in the background, get the current progress of one
+        async fn get_campaign(
+            &self,
+            request: tonic::Request<super::GetCampaignRequest>,
+        ) -> std::result::Result<tonic::Response<super::Campaign>, tonic::Status>;
+        /// Server streaming response type for the WatchCampaign method.
+        type WatchCampaignStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::Campaign, tonic::Status>,
+            > + std::marker::Send
+            + 'static;
+        /// stream the progress of a campaign until it has completed or failed
+        async fn watch_campaign(
+            &self,
+            request: tonic::Request<super::WatchCampaignRequest>,
+        ) -> std::result::Result<tonic::Response<Self::WatchCampaignStream>, tonic::Status>;
+        /// render a campaign without sending anything, to check its audience and messages
+        async fn preview(
+            &self,
+            request: tonic::Request<super::PreviewRequest>,
+        ) -> std::result::Result<tonic::Response<super::PreviewResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct CrmServer<T> {
@@ -394,6 +757,199 @@ pub mod crm_server {
                     };
                     Box::pin(fut)
                 }
+                "/crm.Crm/RunCampaign" => {
+                    #[allow(non_camel_case_types)]
+                    struct RunCampaignSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::RunCampaignRequest> for RunCampaignSvc<T> {
+                        type Response = super::RunCampaignResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RunCampaignRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Crm>::run_campaign(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RunCampaignSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/crm.Crm/RunSchedule" => {
+                    #[allow(non_camel_case_types)]
+                    struct RunScheduleSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::RunScheduleRequest> for RunScheduleSvc<T> {
+                        type Response = super::RunCampaignResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RunScheduleRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Crm>::run_schedule(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RunScheduleSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/crm.Crm/GetCampaign" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetCampaignSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::GetCampaignRequest> for GetCampaignSvc<T> {
+                        type Response = super::Campaign;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetCampaignRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Crm>::get_campaign(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetCampaignSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/crm.Crm/WatchCampaign" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchCampaignSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::ServerStreamingService<super::WatchCampaignRequest>
+                        for WatchCampaignSvc<T>
+                    {
+                        type Response = super::Campaign;
+                        type ResponseStream = T::WatchCampaignStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchCampaignRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Crm>::watch_campaign(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = WatchCampaignSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/crm.Crm/Preview" => {
+                    #[allow(non_camel_case_types)]
+                    struct PreviewSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::PreviewRequest> for PreviewSvc<T> {
+                        type Response = super::PreviewResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PreviewRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as Crm>::preview(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = PreviewSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     let mut response = http::Response::new(empty_body());
                     let headers = response.headers_mut();
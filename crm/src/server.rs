@@ -22,7 +22,9 @@ async fn main() -> Result<()> {
     let port = &config.server.port;
     let addr = format!("[::1]:{port}").parse()?;
     info!("CRM service listening on {}", addr);
-    let svc = CrmService::try_new(config).await?.into_server()?;
+    let svc = CrmService::try_new(config).await?;
+    svc.start_scheduler();
+    let svc = svc.into_server()?;
 
     if let Some(tls) = tls {
         info!("TLS enabled");
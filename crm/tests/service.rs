@@ -0,0 +1,1426 @@
+#![allow(clippy::result_large_err)]
+
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use crm::pb::{
+    preview_request, Campaign, CampaignStatus, ChannelStats, GetCampaignRequestBuilder,
+    PreviewRequest, RecallRequestBuilder, RemindRequestBuilder, RunCampaignRequestBuilder,
+    RunScheduleRequestBuilder, VariantBuilder, WatchCampaignRequestBuilder, WelcomeRequest,
+    WelcomeRequestBuilder,
+};
+use crm::{
+    AppConfig, CampaignState, CampaignStore, CrmService, MemoryCampaignStore, ScheduleConfig,
+    ScheduledCampaign,
+};
+use crm_metadata::pb::metadata_server::{Metadata, MetadataServer};
+use crm_metadata::pb::{
+    Content, CreateContentRequest, CreatePublisherRequest, DeleteContentRequest,
+    DeletePublisherRequest, DeleteResponse, ListContentsRequest, ListContentsResponse,
+    ListPublishersRequest, ListPublishersResponse, MaterializeRequest, MaterializeResponse,
+    Publisher, RecommendRequest, RecommendResponse, SearchRequest, SearchResponse,
+    UpdateContentRequest, UpdatePublisherRequest,
+};
+use crm_metadata::TemplateSource;
+use crm_send::pb::notification_server::{Notification, NotificationServer};
+use crm_send::pb::send_request::Msg;
+use crm_send::pb::{SendRequest, SendResponse};
+use futures::{stream, Stream, StreamExt};
+use prost_types::Timestamp;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{async_trait, Code, Request, Response, Status, Streaming};
+use user_stat::pb::activity_event::Event;
+use user_stat::pb::filter::Expr;
+use user_stat::pb::user_stats_server::{UserStats, UserStatsServer};
+use user_stat::pb::{
+    ActivityEvent, CountResponse, Filter, Gender, IngestResponse, NotificationChannel,
+    QueryPageRequest, QueryPageResponse, QueryRequest, RawQueryRequest, RecordNotificationRequest,
+    RecordResponse, RecordVisitRequest, RecordWatchRequest, UpsertUserRequest, User,
+};
+
+const PORT_BASE: u32 = 61000;
+/// messages to this address are always rejected by the fake notification service
+const UNDELIVERABLE: &str = "mallory@acme.org";
+/// the fake user stats fails the query stream when it gets to this user
+const UNREADABLE: &str = "trudy@acme.org";
+const SENT_AT: Timestamp = Timestamp {
+    seconds: 1714521600,
+    nanos: 0,
+};
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tokio::test]
+async fn welcome_should_send_contents_to_registered_users() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE, users(&["alice", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-1")
+        .interval(7u32)
+        .content_ids([1u32, 2])
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    assert_eq!(resp.id, "welcome-1");
+    wait(&svc, &resp.id).await?;
+
+    let queries = fakes.queries.lock().unwrap();
+    assert_eq!(queries.len(), 1);
+    assert!(queries[0].timestamps.contains_key("created_at"));
+
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(recipients(&sent), ["alice@acme.org", "bob@acme.org"]);
+    assert_eq!(subjects(&sent), ["Welcome", "Welcome"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recall_should_send_contents_to_inactive_users() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 10, users(&["alice", "bob", "carol"])).await?;
+    let req = RecallRequestBuilder::default()
+        .id("recall-1")
+        .last_visit_interval(30u32)
+        .content_ids([3u32, 4, 5])
+        .build()?;
+
+    let resp = svc.recall(req).await?.into_inner();
+    assert_eq!(resp.id, "recall-1");
+    wait(&svc, &resp.id).await?;
+
+    let queries = fakes.queries.lock().unwrap();
+    assert_eq!(queries.len(), 1);
+    let tq = &queries[0].timestamps["last_visited_at"];
+    let lower = tq.lower.as_ref().unwrap().seconds;
+    let upper = tq.upper.as_ref().unwrap().seconds;
+    assert_eq!(upper - lower, 24 * 60 * 60);
+
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(
+        recipients(&sent),
+        ["alice@acme.org", "bob@acme.org", "carol@acme.org"]
+    );
+    for body in bodies(&sent) {
+        for id in [3, 4, 5] {
+            assert!(body.contains(&format!("content-{id}")));
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recall_should_send_each_user_their_recommended_contents() -> Result<()> {
+    let mut users = users(&["alice", "bob", "carol"]);
+    users[0].recent_watched = vec![1];
+    users[0].finished = vec![2];
+    users[1].finished = FAKE_CATALOG.to_vec();
+    let (svc, fakes) = start_crm(PORT_BASE + 180, users).await?;
+    let req = RecallRequestBuilder::default()
+        .id("recall-3")
+        .last_visit_interval(30u32)
+        .recommend(2u32)
+        .build()?;
+
+    let resp = svc.recall(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!((campaign.sent, campaign.skipped), (2, 1));
+    assert_eq!(campaign.targeted, 3);
+
+    // bob has watched everything, there is nothing to recommend
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(recipients(&sent), ["alice@acme.org", "carol@acme.org"]);
+    let bodies = bodies(&sent);
+    assert!(bodies[0].contains("content-3") && bodies[0].contains("content-4"));
+    assert!(!bodies[0].contains("content-1") && !bodies[0].contains("content-5"));
+    assert!(bodies[1].contains("content-1") && bodies[1].contains("content-2"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recall_with_no_matching_users_should_send_nothing() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 20, vec![]).await?;
+    let req = RecallRequestBuilder::default()
+        .id("recall-2")
+        .last_visit_interval(30u32)
+        .content_ids([3u32])
+        .build()?;
+
+    let resp = svc.recall(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.status(), CampaignStatus::Completed);
+    assert_eq!(campaign.targeted, 0);
+
+    assert!(fakes.sent.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn remind_should_send_each_user_their_unfinished_contents() -> Result<()> {
+    let mut users = users(&["alice", "bob", "carol"]);
+    users[0].started_but_not_finished = vec![7, 8];
+    users[2].started_but_not_finished = vec![9];
+    let (svc, fakes) = start_crm(PORT_BASE + 30, users).await?;
+    let req = RemindRequestBuilder::default()
+        .id("remind-1")
+        .last_visit_interval(14u32)
+        .build()?;
+
+    let resp = svc.remind(req).await?.into_inner();
+    assert_eq!(resp.id, "remind-1");
+    assert_eq!(resp.suppressed, 0);
+    wait(&svc, &resp.id).await?;
+
+    let queries = fakes.queries.lock().unwrap();
+    assert!(queries[0].timestamps.contains_key("last_visited_at"));
+    let Some(Expr::And(filters)) = queries[0].filter.as_ref().and_then(|f| f.expr.as_ref()) else {
+        panic!("remind should filter users without unfinished contents");
+    };
+    assert_eq!(
+        filters.filters[0],
+        Filter::not(Filter::null("started_but_not_finished"))
+    );
+
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(recipients(&sent), ["alice@acme.org", "carol@acme.org"]);
+    let bodies = bodies(&sent);
+    assert!(bodies[0].contains("content-7") && bodies[0].contains("content-8"));
+    assert!(!bodies[0].contains("content-9"));
+    assert!(bodies[1].contains("content-9") && !bodies[1].contains("content-7"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delivered_messages_should_be_recorded_to_user_stats() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 40, users(&["alice", "mallory", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-2")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.status(), CampaignStatus::Completed);
+    assert_eq!(
+        (campaign.targeted, campaign.sent, campaign.failed),
+        (3, 2, 1)
+    );
+    assert_eq!(
+        campaign.channels["email"],
+        ChannelStats { sent: 2, failed: 1 }
+    );
+
+    assert_eq!(
+        recipients(&fakes.sent.lock().unwrap()),
+        ["alice@acme.org", "bob@acme.org"]
+    );
+    let notifications = fakes.notifications.lock().unwrap();
+    let emails: Vec<_> = notifications.iter().map(|n| n.email.as_str()).collect();
+    assert_eq!(emails, ["alice@acme.org", "bob@acme.org"]);
+    for n in notifications.iter() {
+        assert_eq!(n.channel(), NotificationChannel::Email);
+        assert_eq!(n.at, Some(SENT_AT));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recently_notified_users_should_be_suppressed() -> Result<()> {
+    let (svc, fakes) =
+        start_crm_with_notified(PORT_BASE + 50, users(&["alice", "bob", "carol"]), &["bob"])
+            .await?;
+    let req = RecallRequestBuilder::default()
+        .id("recall-3")
+        .last_visit_interval(30u32)
+        .content_ids([3u32])
+        .build()?;
+
+    let resp = svc.recall(req).await?.into_inner();
+    assert_eq!(resp.suppressed, 1);
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!((campaign.targeted, campaign.suppressed), (2, 1));
+
+    // crm.yml caps email at 72 hours
+    let queries = fakes.queries.lock().unwrap();
+    let Some(Expr::Or(cap)) = queries[0].filter.as_ref().and_then(|f| f.expr.as_ref()) else {
+        panic!("frequency cap is not applied");
+    };
+    let Some(Expr::Time(tf)) = &cap.filters[1].expr else {
+        panic!("frequency cap has no time window");
+    };
+    assert_eq!(tf.column, "last_email_notification");
+    let cutoff = tf.upper.as_ref().unwrap().seconds;
+    let expected = Utc::now().timestamp() - 72 * 60 * 60;
+    assert!((cutoff - expected).abs() < 60);
+
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(recipients(&sent), ["alice@acme.org", "carol@acme.org"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn replayed_request_should_not_send_again() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 60, users(&["alice", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-3")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+
+    let first = svc.welcome(req.clone()).await?.into_inner();
+    // replayed while running, and after completed
+    let second = svc.welcome(req.clone()).await?.into_inner();
+    wait(&svc, &first.id).await?;
+    let third = svc.welcome(req).await?.into_inner();
+    assert_eq!(first, second);
+    assert_eq!(first, third);
+    let campaign = wait(&svc, &first.id).await?;
+    assert_eq!(campaign.status(), CampaignStatus::Completed);
+
+    // the replays keep the progress of the delivered campaign
+    let req = GetCampaignRequestBuilder::default().id(&first.id).build()?;
+    let campaign = svc.get_campaign(req).await?.into_inner();
+    assert_eq!(campaign.status(), CampaignStatus::Completed);
+    assert_eq!(campaign.targeted, 2);
+    assert_eq!(campaign.sent, 2);
+    assert_eq!(campaign.failed, 0);
+
+    assert_eq!(fakes.queries.lock().unwrap().len(), 1);
+    assert_eq!(fakes.sent.lock().unwrap().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn finished_campaigns_should_be_read_from_ledger() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 200, users(&["alice", "bob"])).await?;
+    // finished by an earlier run of the service
+    let finished = Campaign {
+        targeted: 3,
+        sent: 2,
+        skipped: 1,
+        ..Campaign::new("welcome-earlier", CampaignStatus::Completed)
+    };
+    fakes.store.start("welcome-earlier", "").await?;
+    fakes.store.finish("welcome-earlier", &finished).await?;
+
+    let req = GetCampaignRequestBuilder::default()
+        .id("welcome-earlier")
+        .build()?;
+    assert_eq!(svc.get_campaign(req).await?.into_inner(), finished);
+    assert_eq!(wait(&svc, "welcome-earlier").await?, finished);
+
+    // a completed campaign is evicted from the tracker, its progress comes from the ledger
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-4")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let resp = svc.welcome(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(
+        fakes.store.finished(&resp.id).await?,
+        Some(campaign.clone())
+    );
+    let req = GetCampaignRequestBuilder::default().id(&resp.id).build()?;
+    assert_eq!(svc.get_campaign(req).await?.into_inner(), campaign);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unreachable_users_should_be_skipped() -> Result<()> {
+    let mut users = users(&["alice", "bob"]);
+    users[0].phone = "+15550101".to_string();
+    let (svc, fakes) = start_crm(PORT_BASE + 195, users).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-sms")
+        .interval(7u32)
+        .content_ids([1u32])
+        .channel(NotificationChannel::Sms as i32)
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.targeted, 2);
+    assert_eq!(
+        (campaign.sent, campaign.failed, campaign.skipped),
+        (1, 0, 1)
+    );
+    assert_eq!(fakes.sent.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_should_fail_if_users_could_not_be_read() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 190, users(&["alice", "trudy", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-5")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+
+    let resp = svc.welcome(req.clone()).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.status(), CampaignStatus::Failed);
+    assert_eq!(campaign.error, "connection reset by peer");
+    assert_eq!(recipients(&fakes.sent.lock().unwrap()), ["alice@acme.org"]);
+
+    // not finished in the ledger, so a replay resumes it instead of reporting it completed
+    let resp = svc.welcome(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.status(), CampaignStatus::Failed);
+    assert_eq!(fakes.queries.lock().unwrap().len(), 2);
+    assert_eq!(recipients(&fakes.sent.lock().unwrap()), ["alice@acme.org"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reused_id_should_be_rejected_for_a_different_campaign() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 65, users(&["alice", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-4")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let resp = svc.welcome(req.clone()).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+
+    let other = WelcomeRequest {
+        content_ids: vec![2],
+        ..req
+    };
+    let status = svc.welcome(other).await.unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+    // nor after a restart, when only the ledger knows about the campaign
+    let status = fakes.store.start("welcome-4", "other").await.unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    assert_eq!(fakes.sent.lock().unwrap().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resumed_request_should_only_send_remaining_users() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 70, users(&["alice", "bob", "carol"])).await?;
+    // a previous run crashed after delivering to bob
+    fakes
+        .store
+        .record_sent("recall-4", "bob@acme.org", None)
+        .await?;
+
+    let req = RecallRequestBuilder::default()
+        .id("recall-4")
+        .last_visit_interval(30u32)
+        .content_ids([3u32])
+        .build()?;
+    let resp = svc.recall(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.sent, 2);
+    assert_eq!(campaign.skipped, 1);
+    assert_eq!(campaign.targeted, 3);
+
+    let sent = fakes.sent.lock().unwrap();
+    assert_eq!(recipients(&sent), ["alice@acme.org", "carol@acme.org"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_progress_should_be_watchable() -> Result<()> {
+    let (svc, _fakes) = start_crm(PORT_BASE + 80, users(&["alice", "bob"])).await?;
+    let req = WelcomeRequestBuilder::default()
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+
+    // requests without an id get a generated one
+    let resp = svc.welcome(req).await?.into_inner();
+    assert!(!resp.id.is_empty());
+
+    let req = WatchCampaignRequestBuilder::default()
+        .id(&resp.id)
+        .build()?;
+    let updates: Vec<_> = svc
+        .watch_campaign(req)
+        .await?
+        .into_inner()
+        .map(|c| c.unwrap())
+        .collect()
+        .await;
+    let last = updates.last().unwrap();
+    assert_eq!(last.status(), CampaignStatus::Completed);
+    assert_eq!(last.sent, 2);
+    // sent never goes backwards
+    assert!(updates.windows(2).all(|w| w[0].sent <= w[1].sent));
+
+    let req = GetCampaignRequestBuilder::default().id(&resp.id).build()?;
+    assert_eq!(&svc.get_campaign(req).await?.into_inner(), last);
+
+    let req = GetCampaignRequestBuilder::default().id("unknown").build()?;
+    assert_eq!(
+        svc.get_campaign(req).await.unwrap_err().code(),
+        Code::NotFound
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn preview_should_render_messages_without_sending() -> Result<()> {
+    let (svc, fakes) = start_crm_with_notified(
+        PORT_BASE + 90,
+        users(&["alice", "bob", "carol", "dave"]),
+        &["bob"],
+    )
+    .await?;
+    let req = RecallRequestBuilder::default()
+        .id("recall-preview")
+        .last_visit_interval(30u32)
+        .content_ids([1u32, 2])
+        .build()?;
+    let req = PreviewRequest {
+        campaign: Some(preview_request::Campaign::Recall(req)),
+        sample_size: 2,
+    };
+
+    let resp = svc.preview(req).await?.into_inner();
+    assert_eq!(resp.targeted, 3);
+    assert_eq!(resp.suppressed, 1);
+    assert_eq!(
+        recipients(&resp.samples),
+        ["alice@acme.org", "carol@acme.org"]
+    );
+    assert_eq!(subjects(&resp.samples), ["We miss you"; 2]);
+
+    assert!(fakes.sent.lock().unwrap().is_empty());
+    assert!(fakes.notifications.lock().unwrap().is_empty());
+    assert_eq!(
+        fakes.store.start("recall-preview", "").await?,
+        CampaignState::New
+    );
+    let req = GetCampaignRequestBuilder::default()
+        .id("recall-preview")
+        .build()?;
+    assert_eq!(
+        svc.get_campaign(req).await.unwrap_err().code(),
+        Code::NotFound
+    );
+
+    let req = PreviewRequest {
+        campaign: None,
+        sample_size: 0,
+    };
+    let err = svc.preview(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn preview_should_render_each_user_their_unfinished_contents() -> Result<()> {
+    let mut users = users(&["alice", "bob"]);
+    users[0].started_but_not_finished = vec![7];
+    users[1].started_but_not_finished = vec![8];
+    let (svc, fakes) = start_crm(PORT_BASE + 100, users).await?;
+    let req = RemindRequestBuilder::default()
+        .last_visit_interval(14u32)
+        .build()?;
+    let req = PreviewRequest {
+        campaign: Some(preview_request::Campaign::Remind(req)),
+        sample_size: 0,
+    };
+
+    let resp = svc.preview(req).await?.into_inner();
+    assert_eq!(resp.targeted, 2);
+    let bodies = bodies(&resp.samples);
+    assert!(bodies[0].contains("content-7") && !bodies[0].contains("content-8"));
+    assert!(bodies[1].contains("content-8") && !bodies[1].contains("content-7"));
+    assert!(fakes.sent.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn defined_campaign_should_run_by_name() -> Result<()> {
+    let mut users = users(&["alice", "bob"]);
+    users[1].started_but_not_finished = vec![9];
+    let (svc, fakes) = start_crm(PORT_BASE + 110, users).await?;
+    let req = RunCampaignRequestBuilder::default()
+        .id("continue-1")
+        .name("continue_watching")
+        .build()?;
+
+    let resp = svc.run_campaign(req).await?.into_inner();
+    assert_eq!(resp.id, "continue-1");
+    wait(&svc, &resp.id).await?;
+
+    let queries = fakes.queries.lock().unwrap().clone();
+    let Some(Expr::And(filters)) = queries[0].filter.as_ref().and_then(|f| f.expr.as_ref()) else {
+        panic!("campaign should query its segment");
+    };
+    // the segment, narrowed down by the frequency cap
+    let Some(Expr::And(segment)) = filters.filters[0].expr.as_ref() else {
+        panic!("segment should be a conjunction of its filters");
+    };
+    assert_eq!(
+        segment.filters[1],
+        Filter::not(Filter::null("started_but_not_finished"))
+    );
+
+    let sent = fakes.sent.lock().unwrap().clone();
+    assert_eq!(recipients(&sent), ["bob@acme.org"]);
+    assert_eq!(subjects(&sent), ["Continue watching"]);
+    assert!(bodies(&sent)[0].contains("content-9"));
+
+    let req = PreviewRequest {
+        campaign: Some(preview_request::Campaign::Name("weekly_picks".to_string())),
+        sample_size: 0,
+    };
+    let resp = svc.preview(req).await?.into_inner();
+    assert_eq!(subjects(&resp.samples), ["Picks of the week"; 2]);
+
+    let req = RunCampaignRequestBuilder::default()
+        .name("unknown")
+        .build()?;
+    let err = svc.run_campaign(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduler_should_run_campaigns() -> Result<()> {
+    let (svc, fakes) =
+        start_crm_with_config(PORT_BASE + 125, users(&["alice", "bob"]), &[], |config| {
+            let schedule = ScheduleConfig {
+                cron: "* * * * * *".to_string(),
+                run: ScheduledCampaign::Welcome {
+                    interval: 7,
+                    content_ids: vec![1],
+                },
+                channels: vec![],
+                template: None,
+            };
+            config
+                .schedules
+                .insert("every_second".to_string(), schedule);
+        })
+        .await?;
+
+    svc.start_scheduler();
+    let mut last_run = None;
+    for _ in 0..50 {
+        sleep(Duration::from_millis(100)).await;
+        last_run = fakes.store.last_run("every_second").await?;
+        if last_run.is_some() {
+            break;
+        }
+    }
+
+    let at = last_run.expect("schedule should have run");
+    let id = format!("every_second-{}", at.format("%Y%m%dT%H%M%SZ"));
+    let campaign = wait(&svc, &id).await?;
+    assert_eq!(campaign.status, CampaignStatus::Completed as i32);
+    assert_eq!(campaign.sent, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn due_schedules_should_run_once_for_missed_runs() -> Result<()> {
+    let (svc, fakes) = start_crm(PORT_BASE + 120, users(&["alice", "bob"])).await?;
+    let now = Utc.with_ymd_and_hms(2024, 11, 5, 10, 0, 0).unwrap();
+    let nine = Utc.with_ymd_and_hms(2024, 11, 5, 9, 0, 0).unwrap();
+    // the daily welcome has been down for three days, the others have never run
+    fakes
+        .store
+        .record_run("daily_welcome", nine - chrono::Duration::days(3))
+        .await?;
+
+    let ids = svc.run_due_schedules(now).await?;
+    assert_eq!(ids, ["daily_welcome-20241105T090000Z"]);
+    let req = GetCampaignRequestBuilder::default().id(&ids[0]).build()?;
+    let campaign = svc.get_campaign(req).await?.into_inner();
+    assert_eq!(campaign.status(), CampaignStatus::Completed);
+    assert_eq!(campaign.sent, 2);
+    assert_eq!(fakes.store.last_run("daily_welcome").await?, Some(nine));
+    assert_eq!(fakes.store.last_run("daily_remind").await?, None);
+
+    // nothing is due until tomorrow
+    assert!(svc.run_due_schedules(now).await?.is_empty());
+    let tomorrow = nine + chrono::Duration::days(1);
+    let ids = svc.run_due_schedules(tomorrow).await?;
+    assert_eq!(ids, ["daily_welcome-20241106T090000Z"]);
+    assert_eq!(fakes.sent.lock().unwrap().len(), 4);
+
+    let req = RunScheduleRequestBuilder::default()
+        .id("welcome-now")
+        .name("daily_welcome")
+        .build()?;
+    let resp = svc.run_schedule(req).await?.into_inner();
+    assert_eq!(resp.id, "welcome-now");
+    assert_eq!(wait(&svc, &resp.id).await?.sent, 2);
+    // running now does not affect the schedule
+    assert_eq!(fakes.store.last_run("daily_welcome").await?, Some(tomorrow));
+
+    let req = RunScheduleRequestBuilder::default()
+        .name("unknown")
+        .build()?;
+    let err = svc.run_schedule(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn users_should_be_notified_via_their_first_available_channel() -> Result<()> {
+    let mut users = users(&["alice", "bob", "carol"]);
+    users[0].device_id = "device-alice".to_string();
+    users[0].phone = "+15550101".to_string();
+    users[1].phone = "+15550102".to_string();
+    let (svc, fakes) = start_crm(PORT_BASE + 130, users).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-channels")
+        .interval(7u32)
+        .content_ids([1u32])
+        .channel(NotificationChannel::InApp as i32)
+        .channel(NotificationChannel::Sms as i32)
+        .channel(NotificationChannel::Email as i32)
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.sent, 3);
+    for channel in ["in_app", "sms", "email"] {
+        let stats = ChannelStats { sent: 1, failed: 0 };
+        assert_eq!(campaign.channels[channel], stats);
+    }
+
+    let sent = fakes.sent.lock().unwrap().clone();
+    let Some(Msg::InApp(in_app)) = sent.iter().find_map(|r| match &r.msg {
+        Some(Msg::InApp(_)) => r.msg.clone(),
+        _ => None,
+    }) else {
+        panic!("alice should get an in-app message");
+    };
+    assert_eq!(in_app.device_id, "device-alice");
+    assert_eq!(in_app.title, "Welcome");
+    let Some(Msg::Sms(sms)) = sent.iter().find_map(|r| match &r.msg {
+        Some(Msg::Sms(_)) => r.msg.clone(),
+        _ => None,
+    }) else {
+        panic!("bob should get an sms");
+    };
+    assert_eq!(sms.recipients, ["+15550102"]);
+    assert_eq!(sms.sender, "+15550100");
+    assert_eq!(recipients(&sent), ["carol@acme.org"]);
+
+    let mut notified: Vec<_> = fakes
+        .notifications
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|n| (n.email.clone(), n.channel()))
+        .collect();
+    notified.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        notified,
+        [
+            ("alice@acme.org".to_string(), NotificationChannel::InApp),
+            ("bob@acme.org".to_string(), NotificationChannel::Sms),
+            ("carol@acme.org".to_string(), NotificationChannel::Email),
+        ]
+    );
+
+    let req = WelcomeRequestBuilder::default()
+        .interval(7u32)
+        .channel(NotificationChannel::Unspecified as i32)
+        .build()?;
+    let err = svc.welcome(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn users_should_be_split_into_variants() -> Result<()> {
+    let names = [
+        "alice", "bob", "carol", "dave", "erin", "frank", "grace", "heidi",
+    ];
+    let (svc, fakes) = start_crm(PORT_BASE + 140, users(&names)).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-variants")
+        .interval(7u32)
+        .content_ids([1u32])
+        .variant(VariantBuilder::default().name("a").build()?)
+        .variant(
+            VariantBuilder::default()
+                .name("b")
+                .weight(3u32)
+                .subject("Hello")
+                .content_ids([2u32])
+                .build()?,
+        )
+        .build()?;
+
+    let resp = svc.welcome(req.clone()).await?.into_inner();
+    let campaign = wait(&svc, &resp.id).await?;
+    assert_eq!(campaign.sent, names.len() as u64);
+
+    // every recipient is recorded with the variant they got
+    let variants = fakes.store.variants(&resp.id).await?;
+    assert_eq!(variants.len(), names.len());
+    let sent = fakes.sent.lock().unwrap().clone();
+    for (email, subject) in recipients(&sent).iter().zip(subjects(&sent)) {
+        let expected = if variants[email] == "a" {
+            "Welcome"
+        } else {
+            "Hello"
+        };
+        assert_eq!(subject, expected);
+    }
+    for (name, stats) in &campaign.variants {
+        let n = variants.values().filter(|v| *v == name).count() as u64;
+        assert_eq!(*stats, ChannelStats { sent: n, failed: 0 });
+    }
+
+    // the same users get the same variants every time
+    let (svc, fakes) = start_crm(PORT_BASE + 145, users(&names)).await?;
+    let resp = svc.welcome(req).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+    assert_eq!(fakes.store.variants(&resp.id).await?, variants);
+
+    let req = RemindRequestBuilder::default()
+        .last_visit_interval(3u32)
+        .variant(VariantBuilder::default().name("a").build()?)
+        .variant(VariantBuilder::default().name("a").build()?)
+        .build()?;
+    let err = svc.remind(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    let req = RemindRequestBuilder::default()
+        .last_visit_interval(3u32)
+        .variant(
+            VariantBuilder::default()
+                .name("a")
+                .content_ids([1u32])
+                .build()?,
+        )
+        .build()?;
+    let err = svc.remind(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn messages_should_be_rendered_with_named_templates() -> Result<()> {
+    let (svc, fakes) = start_crm_with_config(PORT_BASE + 150, users(&["alice"]), &[], |config| {
+        let source = TemplateSource {
+            html: Some("<b>{{ user.name }}</b>".to_string()),
+            text: Some(
+                "Hi {{ user.name }}: {% for c in contents %}{{ c.name }}{% endfor %}".to_string(),
+            ),
+            ..Default::default()
+        };
+        config
+            .templates
+            .inline
+            .insert("greeting".to_string(), source);
+    })
+    .await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-templates")
+        .interval(7u32)
+        .content_ids([1u32])
+        .variant(
+            VariantBuilder::default()
+                .name("a")
+                .template("greeting")
+                .build()?,
+        )
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+    let sent = fakes.sent.lock().unwrap().clone();
+    let Some(Msg::Email(email)) = &sent[0].msg else {
+        panic!("alice should get an email");
+    };
+    assert_eq!(email.body, "Hi alice: content-1");
+    assert_eq!(email.html_body, "<b>alice</b>");
+
+    let req = WelcomeRequestBuilder::default()
+        .interval(7u32)
+        .variant(
+            VariantBuilder::default()
+                .name("a")
+                .template("missing")
+                .build()?,
+        )
+        .build()?;
+    let err = svc.welcome(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn messages_should_be_personalized_for_each_user() -> Result<()> {
+    let mut users = users(&["alice", "bob"]);
+    users[0].gender = Gender::Female as i32;
+    users[0].finished = vec![1, 2];
+    users[1].name = String::new();
+    let (svc, fakes) = start_crm_with_config(PORT_BASE + 160, users, &[], |config| {
+        let source = TemplateSource {
+            text: Some(
+                "Hi {{ user.name | default('there') }}\
+                {% if user.gender == 'female' %}, queen{% endif %}: \
+                {{ user.finished | length }} finished"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        config
+            .templates
+            .inline
+            .insert("greeting".to_string(), source);
+    })
+    .await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-personalized")
+        .interval(7u32)
+        .content_ids([1u32])
+        .variant(
+            VariantBuilder::default()
+                .name("a")
+                .template("greeting")
+                .build()?,
+        )
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+    let mut bodies = bodies(&fakes.sent.lock().unwrap());
+    bodies.sort();
+    assert_eq!(
+        bodies,
+        ["Hi alice, queen: 2 finished", "Hi there: 0 finished"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn messages_should_be_localized_for_each_user() -> Result<()> {
+    let mut users = users(&["alice", "bob", "carol"]);
+    users[0].locale = "zh-TW".to_string();
+    users[1].locale = "en-US".to_string();
+    let (svc, fakes) = start_crm_with_config(PORT_BASE + 170, users, &[], |config| {
+        let source: TemplateSource = serde_yaml::from_str(
+            r#"
+subject: Welcome aboard
+text: Hello
+locales:
+  zh:
+    subject: 欢迎
+    text: 你好
+  zh-TW:
+    text: 您好
+"#,
+        )
+        .unwrap();
+        config
+            .templates
+            .inline
+            .insert("greeting".to_string(), source);
+    })
+    .await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-localized")
+        .interval(7u32)
+        .content_ids([1u32])
+        .template("greeting")
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+    let sent = fakes.sent.lock().unwrap().clone();
+    let mut messages: Vec<_> = recipients(&sent)
+        .into_iter()
+        .zip(subjects(&sent).into_iter().zip(bodies(&sent)))
+        .collect();
+    messages.sort();
+    let expected = [
+        ("alice@acme.org", "欢迎", "您好"),
+        ("bob@acme.org", "Welcome aboard", "Hello"),
+        ("carol@acme.org", "Welcome aboard", "Hello"),
+    ]
+    .map(|(email, subject, body)| (email.to_string(), (subject.to_string(), body.to_string())));
+    assert_eq!(messages, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn builtin_messages_should_be_localized_for_each_user() -> Result<()> {
+    let mut users = users(&["alice", "bob", "carol"]);
+    users[0].locale = "zh-Hant-TW".to_string();
+    users[1].locale = "zh-CN".to_string();
+    let (svc, fakes) = start_crm(PORT_BASE + 205, users).await?;
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-builtin-localized")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+
+    let resp = svc.welcome(req).await?.into_inner();
+    wait(&svc, &resp.id).await?;
+    let sent = fakes.sent.lock().unwrap().clone();
+    let mut messages: Vec<_> = recipients(&sent)
+        .into_iter()
+        .zip(subjects(&sent).into_iter().zip(bodies(&sent)))
+        .collect();
+    messages.sort();
+    let greetings: Vec<_> = messages
+        .iter()
+        .map(|(email, (subject, body))| {
+            let greeting = ["您好", "你好", "Hi"]
+                .into_iter()
+                .find(|greeting| body.contains(greeting));
+            (email.as_str(), subject.as_str(), greeting)
+        })
+        .collect();
+    assert_eq!(
+        greetings,
+        [
+            ("alice@acme.org", "歡迎", Some("您好")),
+            ("bob@acme.org", "欢迎", Some("你好")),
+            ("carol@acme.org", "Welcome", Some("Hi")),
+        ]
+    );
+
+    Ok(())
+}
+
+/// Shared handles to what the stand-in services have received.
+#[derive(Clone, Default)]
+struct Fakes {
+    queries: Arc<Mutex<Vec<QueryRequest>>>,
+    sent: Arc<Mutex<Vec<SendRequest>>>,
+    notifications: Arc<Mutex<Vec<RecordNotificationRequest>>>,
+    store: Arc<MemoryCampaignStore>,
+}
+
+struct FakeUserStats {
+    users: Vec<User>,
+    /// emails of users notified within the frequency cap window
+    notified: Vec<String>,
+    queries: Arc<Mutex<Vec<QueryRequest>>>,
+    notifications: Arc<Mutex<Vec<RecordNotificationRequest>>>,
+}
+
+struct FakeMetadata;
+
+/// contents recommended by the fake metadata, the most popular first
+const FAKE_CATALOG: [u32; 5] = [1, 2, 3, 4, 5];
+
+struct FakeNotification {
+    sent: Arc<Mutex<Vec<SendRequest>>>,
+}
+
+impl FakeUserStats {
+    /// all users, except the notified ones if the query applies a frequency cap
+    fn matches(&self, query: &QueryRequest) -> Vec<User> {
+        let capped = query.filter.as_ref().is_some_and(is_capped);
+        self.users
+            .iter()
+            .filter(|u| !(capped && self.notified.contains(&u.email)))
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_capped(filter: &Filter) -> bool {
+    match &filter.expr {
+        Some(Expr::And(list)) | Some(Expr::Or(list)) => list.filters.iter().any(is_capped),
+        Some(Expr::Not(filter)) => is_capped(filter),
+        Some(Expr::Time(f)) => f.column.ends_with("_notification"),
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl UserStats for FakeUserStats {
+    type QueryStream = BoxStream<User>;
+
+    async fn query(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::QueryStream>, Status> {
+        let query = request.into_inner();
+        let users = self
+            .matches(&query)
+            .into_iter()
+            .map(|u| match u.email.as_str() {
+                UNREADABLE => Err(Status::internal("connection reset by peer")),
+                _ => Ok(u),
+            });
+        self.queries.lock().unwrap().push(query);
+        Ok(Response::new(Box::pin(stream::iter(users))))
+    }
+
+    type RawQueryStream = BoxStream<User>;
+
+    async fn raw_query(
+        &self,
+        _request: Request<RawQueryRequest>,
+    ) -> Result<Response<Self::RawQueryStream>, Status> {
+        Err(Status::unimplemented("raw query is not supported"))
+    }
+
+    async fn count(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let count = self.matches(request.get_ref()).len();
+        Ok(Response::new(CountResponse { count: count as _ }))
+    }
+
+    async fn query_page(
+        &self,
+        _request: Request<QueryPageRequest>,
+    ) -> Result<Response<QueryPageResponse>, Status> {
+        Err(Status::unimplemented("query page is not supported"))
+    }
+
+    async fn upsert_user(
+        &self,
+        _request: Request<UpsertUserRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented("upsert user is not supported"))
+    }
+
+    async fn record_visit(
+        &self,
+        _request: Request<RecordVisitRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented("record visit is not supported"))
+    }
+
+    async fn record_view(
+        &self,
+        _request: Request<RecordWatchRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented("record view is not supported"))
+    }
+
+    async fn record_watch_started(
+        &self,
+        _request: Request<RecordWatchRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented(
+            "record watch started is not supported",
+        ))
+    }
+
+    async fn record_watch_finished(
+        &self,
+        _request: Request<RecordWatchRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented(
+            "record watch finished is not supported",
+        ))
+    }
+
+    async fn record_notification(
+        &self,
+        _request: Request<RecordNotificationRequest>,
+    ) -> Result<Response<RecordResponse>, Status> {
+        Err(Status::unimplemented(
+            "record notification is not supported",
+        ))
+    }
+
+    async fn ingest(
+        &self,
+        request: Request<Streaming<ActivityEvent>>,
+    ) -> Result<Response<IngestResponse>, Status> {
+        let mut events = request.into_inner();
+        let mut resp = IngestResponse::default();
+        while let Some(event) = events.next().await {
+            match event?.event {
+                Some(Event::Notification(req)) => {
+                    self.notifications.lock().unwrap().push(req);
+                    resp.accepted += 1;
+                }
+                _ => resp.rejected += 1,
+            }
+        }
+        Ok(Response::new(resp))
+    }
+}
+
+#[async_trait]
+impl Metadata for FakeMetadata {
+    type MaterializeStream = BoxStream<MaterializeResponse>;
+
+    async fn materialize(
+        &self,
+        request: Request<Streaming<MaterializeRequest>>,
+    ) -> Result<Response<Self::MaterializeStream>, Status> {
+        let stream = request.into_inner().map(|req| {
+            req.map(|req| {
+                let content = Content {
+                    id: req.id,
+                    name: format!("content-{}", req.id),
+                    ..Default::default()
+                };
+                MaterializeResponse::new(req.id, Ok(content))
+            })
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn create_content(
+        &self,
+        _request: Request<CreateContentRequest>,
+    ) -> Result<Response<Content>, Status> {
+        Err(Status::unimplemented("create content is not supported"))
+    }
+
+    async fn update_content(
+        &self,
+        _request: Request<UpdateContentRequest>,
+    ) -> Result<Response<Content>, Status> {
+        Err(Status::unimplemented("update content is not supported"))
+    }
+
+    async fn delete_content(
+        &self,
+        _request: Request<DeleteContentRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        Err(Status::unimplemented("delete content is not supported"))
+    }
+
+    async fn list_contents(
+        &self,
+        _request: Request<ListContentsRequest>,
+    ) -> Result<Response<ListContentsResponse>, Status> {
+        Err(Status::unimplemented("list contents is not supported"))
+    }
+
+    async fn create_publisher(
+        &self,
+        _request: Request<CreatePublisherRequest>,
+    ) -> Result<Response<Publisher>, Status> {
+        Err(Status::unimplemented("create publisher is not supported"))
+    }
+
+    async fn update_publisher(
+        &self,
+        _request: Request<UpdatePublisherRequest>,
+    ) -> Result<Response<Publisher>, Status> {
+        Err(Status::unimplemented("update publisher is not supported"))
+    }
+
+    async fn delete_publisher(
+        &self,
+        _request: Request<DeletePublisherRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        Err(Status::unimplemented("delete publisher is not supported"))
+    }
+
+    async fn list_publishers(
+        &self,
+        _request: Request<ListPublishersRequest>,
+    ) -> Result<Response<ListPublishersResponse>, Status> {
+        Err(Status::unimplemented("list publishers is not supported"))
+    }
+
+    async fn recommend(
+        &self,
+        request: Request<RecommendRequest>,
+    ) -> Result<Response<RecommendResponse>, Status> {
+        let req = request.into_inner();
+        let contents = FAKE_CATALOG
+            .iter()
+            .filter(|id| !req.recent_watched.contains(id) && !req.finished.contains(id))
+            .take(req.limit as _)
+            .map(|id| Content {
+                id: *id,
+                name: format!("content-{id}"),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Response::new(RecommendResponse { contents }))
+    }
+
+    async fn search(
+        &self,
+        _request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        Err(Status::unimplemented("search is not supported"))
+    }
+}
+
+#[async_trait]
+impl Notification for FakeNotification {
+    type SendStream = BoxStream<SendResponse>;
+
+    async fn send(
+        &self,
+        request: Request<Streaming<SendRequest>>,
+    ) -> Result<Response<Self::SendStream>, Status> {
+        let sent = self.sent.clone();
+        let stream = request.into_inner().filter_map(move |req| {
+            let ret = req.and_then(|req| {
+                let message_id = match &req.msg {
+                    // a failed item would end the response stream, so just never respond
+                    Some(Msg::Email(email))
+                        if email.recipients.iter().any(|r| r == UNDELIVERABLE) =>
+                    {
+                        return Ok(None)
+                    }
+                    Some(Msg::Email(email)) => email.message_id.clone(),
+                    Some(Msg::Sms(sms)) => sms.message_id.clone(),
+                    Some(Msg::InApp(in_app)) => in_app.message_id.clone(),
+                    None => return Err(Status::invalid_argument("Invalid message")),
+                };
+                sent.lock().unwrap().push(req);
+                Ok(Some(SendResponse {
+                    message_id,
+                    timestamp: Some(SENT_AT),
+                }))
+            });
+            async move { ret.transpose() }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+async fn start_crm(port: u32, users: Vec<User>) -> Result<(CrmService, Fakes)> {
+    start_crm_with_notified(port, users, &[]).await
+}
+
+async fn start_crm_with_notified(
+    port: u32,
+    users: Vec<User>,
+    notified: &[&str],
+) -> Result<(CrmService, Fakes)> {
+    start_crm_with_config(port, users, notified, |_| {}).await
+}
+
+async fn start_crm_with_config(
+    port: u32,
+    users: Vec<User>,
+    notified: &[&str],
+    configure: impl FnOnce(&mut AppConfig),
+) -> Result<(CrmService, Fakes)> {
+    let fakes = Fakes::default();
+
+    let user_stats = FakeUserStats {
+        users,
+        notified: notified.iter().map(|n| format!("{n}@acme.org")).collect(),
+        queries: fakes.queries.clone(),
+        notifications: fakes.notifications.clone(),
+    };
+    let notification = FakeNotification {
+        sent: fakes.sent.clone(),
+    };
+
+    let mut config = AppConfig::try_load()?;
+    config.server.user_stats = serve(port + 1, UserStatsServer::new(user_stats))?;
+    config.server.metadata = serve(port + 2, MetadataServer::new(FakeMetadata))?;
+    config.server.notification = serve(port + 3, NotificationServer::new(notification))?;
+    configure(&mut config);
+
+    let svc = CrmService::try_new_with_store(config, fakes.store.clone()).await?;
+    Ok((svc, fakes))
+}
+
+fn serve<S>(port: u32, svc: S) -> Result<String>
+where
+    S: tonic::codegen::Service<
+            tonic::codegen::http::Request<tonic::body::BoxBody>,
+            Response = tonic::codegen::http::Response<tonic::body::BoxBody>,
+            Error = std::convert::Infallible,
+        > + tonic::server::NamedService
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let addr = format!("[::1]:{port}").parse()?;
+    // bind before returning so that the service could connect right away
+    let incoming = TcpIncoming::new(addr, true, None).map_err(|e| anyhow::anyhow!(e))?;
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(svc)
+            .serve_with_incoming(incoming)
+            .await
+            .expect("Failed to start server");
+    });
+    Ok(format!("http://{addr}"))
+}
+
+fn users(names: &[&str]) -> Vec<User> {
+    names
+        .iter()
+        .map(|name| User {
+            email: format!("{name}@acme.org"),
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Wait for the campaign to finish and return its final progress.
+async fn wait(svc: &CrmService, id: &str) -> Result<Campaign> {
+    let req = WatchCampaignRequestBuilder::default().id(id).build()?;
+    let mut updates = svc.watch_campaign(req).await?.into_inner();
+    let mut campaign = None;
+    while let Some(update) = updates.next().await {
+        campaign = Some(update?);
+    }
+    Ok(campaign.expect("campaign has no progress"))
+}
+
+fn recipients(sent: &[SendRequest]) -> Vec<String> {
+    sent.iter()
+        .filter_map(|req| match &req.msg {
+            Some(Msg::Email(email)) => Some(email.recipients.join(",")),
+            _ => None,
+        })
+        .collect()
+}
+
+fn subjects(sent: &[SendRequest]) -> Vec<String> {
+    sent.iter()
+        .filter_map(|req| match &req.msg {
+            Some(Msg::Email(email)) => Some(email.subject.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn bodies(sent: &[SendRequest]) -> Vec<String> {
+    sent.iter()
+        .filter_map(|req| match &req.msg {
+            Some(Msg::Email(email)) => Some(email.body.clone()),
+            _ => None,
+        })
+        .collect()
+}
@@ -13,7 +13,6 @@ fn main() -> Result<()> {
             true,
             Some(&[r#"#[serde(rename_all = "camelCase")]"#]),
         )
-        .with_sqlx_from_row(&["User"], None)
         .with_derive_builder(
             &[
                 "User",
@@ -21,13 +20,53 @@ fn main() -> Result<()> {
                 "RawQueryRequest",
                 "TimeQuery",
                 "IdQuery",
+                "QueryPageRequest",
+                "UpsertUserRequest",
+                "RecordVisitRequest",
+                "RecordWatchRequest",
+                "RecordNotificationRequest",
             ],
             None,
         )
         .with_field_attributes(
-            &["User.email", "User.name", "RawQueryRequest.query"],
+            &[
+                "User.email",
+                "User.name",
+                "RawQueryRequest.query",
+                "UpsertUserRequest.email",
+                "UpsertUserRequest.name",
+                "RecordVisitRequest.email",
+                "RecordWatchRequest.email",
+                "RecordNotificationRequest.email",
+            ],
             &[r#"#[builder(setter(into))]"#],
         )
+        .with_field_attributes(
+            &[
+                "User.phone",
+                "User.device_id",
+                "User.locale",
+                "QueryPageRequest.cursor",
+                "UpsertUserRequest.phone",
+                "UpsertUserRequest.device_id",
+                "UpsertUserRequest.locale",
+            ],
+            &[r#"#[builder(default, setter(into))]"#],
+        )
+        .with_field_attributes(
+            &[
+                "User.started_but_not_finished",
+                "User.gender",
+                "User.recent_watched",
+                "User.viewed_but_not_started",
+                "User.finished",
+                "IdQuery.mode",
+                "QueryPageRequest.page_size",
+                "UpsertUserRequest.gender",
+                "RecordNotificationRequest.channel",
+            ],
+            &[r#"#[builder(default)]"#],
+        )
         .with_field_attributes(
             &["TimeQuery.before", "TimeQuery.after"],
             &[r#"#[builder(setter(into, strip_option))]"#],
@@ -40,6 +79,16 @@ fn main() -> Result<()> {
             &["QueryRequest.ids"],
             &[r#"#[builder(setter(each(name = "id", into)))]"#],
         )
+        .with_field_attributes(
+            &[
+                "QueryRequest.filter",
+                "QueryPageRequest.query",
+                "RecordVisitRequest.at",
+                "RecordWatchRequest.at",
+                "RecordNotificationRequest.at",
+            ],
+            &[r#"#[builder(default, setter(into, strip_option))]"#],
+        )
         .compile_protos(
             &[
                 "../protos/user-stats/messages.proto",
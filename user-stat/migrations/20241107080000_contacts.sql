@@ -0,0 +1,4 @@
+-- contact data of the channels other than email, NULL if unknown
+ALTER TABLE user_stats
+    ADD COLUMN phone varchar(32),
+    ADD COLUMN device_id varchar(64);
@@ -0,0 +1,2 @@
+-- language tag of the user such as zh-TW, NULL if unknown
+ALTER TABLE user_stats ADD COLUMN locale varchar(16);
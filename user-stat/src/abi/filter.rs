@@ -0,0 +1,299 @@
+#![allow(clippy::result_large_err)]
+
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+use tonic::Status;
+
+use super::query::{
+    column, push_ids, push_time_range, to_ts, CONTACT_COLUMNS, ID_COLUMNS, TIMESTAMP_COLUMNS,
+};
+use crate::pb::{
+    filter::Expr, Filter, FilterList, Gender, GenderFilter, IdFilter, IdOperator, NullFilter,
+    TimeFilter,
+};
+
+impl Filter {
+    pub fn and(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Self::new(Expr::And(FilterList {
+            filters: filters.into_iter().collect(),
+        }))
+    }
+
+    pub fn or(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Self::new(Expr::Or(FilterList {
+            filters: filters.into_iter().collect(),
+        }))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(filter: Filter) -> Self {
+        Self::new(Expr::Not(Box::new(filter)))
+    }
+
+    pub fn time(
+        column: impl Into<String>,
+        lower: Option<DateTime<Utc>>,
+        upper: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self::new(Expr::Time(TimeFilter {
+            column: column.into(),
+            lower: lower.map(to_ts),
+            upper: upper.map(to_ts),
+        }))
+    }
+
+    pub fn ids(column: impl Into<String>, op: IdOperator, ids: &[u32]) -> Self {
+        Self::new(Expr::Ids(IdFilter {
+            column: column.into(),
+            op: op as _,
+            ids: ids.to_vec(),
+        }))
+    }
+
+    pub fn gender(gender: Gender) -> Self {
+        Self::new(Expr::Gender(GenderFilter {
+            gender: gender as _,
+        }))
+    }
+
+    pub fn null(column: impl Into<String>) -> Self {
+        Self::new(Expr::Null(NullFilter {
+            column: column.into(),
+        }))
+    }
+
+    fn new(expr: Expr) -> Self {
+        Self { expr: Some(expr) }
+    }
+
+    /// Push the filter as a single boolean expression, an empty filter is TRUE.
+    pub(super) fn push_to(&self, qb: &mut QueryBuilder<'static, Postgres>) -> Result<(), Status> {
+        let Some(expr) = &self.expr else {
+            qb.push("TRUE");
+            return Ok(());
+        };
+
+        match expr {
+            Expr::And(list) => push_list(qb, &list.filters, " AND ", "TRUE")?,
+            Expr::Or(list) => push_list(qb, &list.filters, " OR ", "FALSE")?,
+            Expr::Not(filter) => {
+                // unlike NOT, `IS NOT TRUE` treats NULL as not matched
+                qb.push("((");
+                filter.push_to(qb)?;
+                qb.push(") IS NOT TRUE)");
+            }
+            Expr::Time(f) => {
+                let name = column(&f.column, &TIMESTAMP_COLUMNS)?;
+                push_time_range(qb, name, f.lower.as_ref(), f.upper.as_ref())?;
+            }
+            Expr::Ids(f) => {
+                let name = column(&f.column, &ID_COLUMNS)?;
+                let op = match f.op() {
+                    IdOperator::Contains => " @> ",
+                    IdOperator::Overlaps => " && ",
+                    IdOperator::ContainedBy => " <@ ",
+                    IdOperator::Unspecified => {
+                        return Err(Status::invalid_argument(format!(
+                            "Id operator for {name} must be specified"
+                        )))
+                    }
+                };
+                push_ids(qb, name, op, &f.ids);
+            }
+            Expr::Gender(f) => {
+                let gender = f
+                    .gender()
+                    .as_sql()
+                    .ok_or_else(|| Status::invalid_argument("Gender must be specified"))?;
+                qb.push("gender = ").push_bind(gender).push("::gender");
+            }
+            Expr::Null(f) => {
+                if let Ok(name) = column(&f.column, &ID_COLUMNS) {
+                    qb.push(format!("({name} IS NULL OR cardinality({name}) = 0)"));
+                } else if let Ok(name) = column(&f.column, &CONTACT_COLUMNS) {
+                    qb.push(format!("({name} IS NULL OR {name} = '')"));
+                } else if f.column == "gender" {
+                    qb.push("gender IS NULL");
+                } else {
+                    let name = column(&f.column, &TIMESTAMP_COLUMNS)?;
+                    qb.push(name).push(" IS NULL");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Gender {
+    /// value of the postgres `gender` enum, None if unspecified
+    pub(super) fn as_sql(&self) -> Option<&'static str> {
+        match self {
+            Gender::Female => Some("female"),
+            Gender::Male => Some("male"),
+            Gender::Unknown => Some("unknown"),
+            Gender::Unspecified => None,
+        }
+    }
+
+    /// the gender of the postgres `gender` enum value
+    pub(super) fn from_sql(value: &str) -> Self {
+        match value {
+            "female" => Gender::Female,
+            "male" => Gender::Male,
+            "unknown" => Gender::Unknown,
+            _ => Gender::Unspecified,
+        }
+    }
+}
+
+fn push_list(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    filters: &[Filter],
+    sep: &str,
+    empty: &str,
+) -> Result<(), Status> {
+    if filters.is_empty() {
+        qb.push(empty);
+        return Ok(());
+    }
+
+    qb.push("(");
+    for (i, filter) in filters.iter().enumerate() {
+        if i > 0 {
+            qb.push(sep);
+        }
+        filter.push_to(qb)?;
+    }
+    qb.push(")");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::QueryRequestBuilder;
+    use crate::test_utils::tq;
+    use crate::UserStatsService;
+    use anyhow::Result;
+    use chrono::TimeZone;
+    use futures::StreamExt as _;
+    use tonic::Code;
+
+    fn date(y: i32, m: u32, d: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap())
+    }
+
+    /// signed up before 2024-01-10 OR visited after 2024-04-25, AND NOT finished content 488449
+    fn segment() -> Filter {
+        Filter::and([
+            Filter::or([
+                Filter::time("created_at", None, date(2024, 1, 10)),
+                Filter::time("last_visited_at", date(2024, 4, 25), None),
+            ]),
+            Filter::not(Filter::ids("finished", IdOperator::Contains, &[488449])),
+        ])
+    }
+
+    #[test]
+    fn filter_should_generate_nested_sql() -> Result<()> {
+        let query = QueryRequestBuilder::default()
+            .timestamp(("created_at".to_string(), tq(Some(120), None)))
+            .filter(segment())
+            .build()?;
+        let qb = query.to_query_builder()?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats \
+            WHERE created_at >= $1 AND ((created_at <= $2 OR last_visited_at >= $3) \
+            AND ((finished @> $4) IS NOT TRUE))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_leaves_should_generate_sql() -> Result<()> {
+        let cases = [
+            (Filter::default(), "TRUE"),
+            (Filter::and([]), "TRUE"),
+            (Filter::or([]), "FALSE"),
+            (
+                Filter::ids("recent_watched", IdOperator::Overlaps, &[1]),
+                "recent_watched && $1",
+            ),
+            (
+                Filter::ids("finished", IdOperator::ContainedBy, &[1]),
+                "finished <@ $1",
+            ),
+            (Filter::gender(Gender::Female), "gender = $1::gender"),
+            (Filter::null("last_watched_at"), "last_watched_at IS NULL"),
+            (
+                Filter::null("finished"),
+                "(finished IS NULL OR cardinality(finished) = 0)",
+            ),
+        ];
+
+        for (filter, expected) in cases {
+            let query = QueryRequestBuilder::default().filter(filter).build()?;
+            let qb = query.to_query_builder()?;
+            let sql = qb.sql();
+            let conditions = sql.split(" WHERE ").nth(1).unwrap_or("TRUE");
+            assert_eq!(conditions, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_with_invalid_leaf_should_be_rejected() -> Result<()> {
+        let cases = [
+            Filter::time("name; DROP TABLE user_stats", None, None),
+            Filter::ids("created_at", IdOperator::Contains, &[1]),
+            Filter::ids("finished", IdOperator::Unspecified, &[1]),
+            Filter::gender(Gender::Unspecified),
+            Filter::null("email"),
+            Filter::not(Filter::or([Filter::null("password")])),
+        ];
+
+        for filter in cases {
+            let query = QueryRequestBuilder::default().filter(filter).build()?;
+            let err = query.to_query_builder().err().unwrap();
+            assert_eq!(err.code(), Code::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn filter_should_select_matching_users() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+
+        let signed_up = Filter::time("created_at", None, date(2024, 1, 10));
+        let visited = Filter::time("last_visited_at", date(2024, 4, 25), None);
+        assert_eq!(count(&svc, signed_up.clone()).await?, 100);
+        assert_eq!(count(&svc, visited.clone()).await?, 45);
+        assert_eq!(count(&svc, Filter::or([signed_up, visited])).await?, 109);
+        assert_eq!(count(&svc, segment()).await?, 108);
+        assert_eq!(count(&svc, Filter::gender(Gender::Unknown)).await?, 116);
+        assert_eq!(count(&svc, Filter::gender(Gender::Male)).await?, 0);
+
+        let unfinished = Filter::null("started_but_not_finished");
+        assert_eq!(count(&svc, unfinished.clone()).await?, 2);
+        assert_eq!(count(&svc, Filter::not(unfinished)).await?, 114);
+
+        Ok(())
+    }
+
+    async fn count(svc: &UserStatsService, filter: Filter) -> Result<usize> {
+        let query = QueryRequestBuilder::default().filter(filter).build()?;
+        let ret = svc
+            .query(query)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        Ok(ret.len())
+    }
+}
@@ -0,0 +1,533 @@
+#![allow(clippy::result_large_err)]
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt as _};
+use prost_types::Timestamp;
+use sqlx::postgres::PgQueryResult;
+use tonic::{Code, Response, Status};
+use tracing::warn;
+
+use super::query::ts_to_utc;
+use crate::{
+    pb::{
+        activity_event::Event, ActivityEvent, IngestResponse, NotificationChannel,
+        RecordNotificationRequest, RecordResponse, RecordVisitRequest, RecordWatchRequest,
+        UpsertUserRequest,
+    },
+    ServiceResult, UserStatsService,
+};
+
+/// max number of ids kept in recent_watched, the most recent one first
+const RECENT_WATCHED_LIMIT: i32 = 50;
+const MAX_EMAIL_LEN: usize = 128;
+const MAX_NAME_LEN: usize = 64;
+const MAX_PHONE_LEN: usize = 32;
+const MAX_DEVICE_ID_LEN: usize = 64;
+const MAX_LOCALE_LEN: usize = 16;
+
+const UPSERT_USER: &str = r#"
+INSERT INTO user_stats (email, name, gender, phone, device_id, locale)
+VALUES ($1, $2, COALESCE($3::gender, 'unknown'), NULLIF($4, ''), NULLIF($5, ''), NULLIF($6, ''))
+ON CONFLICT (email) DO UPDATE SET
+    name = EXCLUDED.name,
+    gender = COALESCE($3::gender, user_stats.gender),
+    phone = COALESCE(NULLIF($4, ''), user_stats.phone),
+    device_id = COALESCE(NULLIF($5, ''), user_stats.device_id),
+    locale = COALESCE(NULLIF($6, ''), user_stats.locale)
+"#;
+
+// timestamps only move forward, so that events arriving out of order do not roll them back
+const RECORD_VISIT: &str = r#"
+UPDATE user_stats SET last_visited_at = GREATEST(last_visited_at, $2)
+WHERE email = $1
+"#;
+
+// a content is only viewed if it has not been started or finished yet
+const RECORD_VIEW: &str = r#"
+UPDATE user_stats SET
+    viewed_but_not_started = CASE
+        WHEN $2 = ANY(COALESCE(viewed_but_not_started, '{}')
+            || COALESCE(started_but_not_finished, '{}')
+            || COALESCE(finished, '{}'))
+        THEN viewed_but_not_started
+        ELSE array_append(viewed_but_not_started, $2)
+    END
+WHERE email = $1
+"#;
+
+// watching a finished content again does not bring it back to started
+const RECORD_WATCH_STARTED: &str = r#"
+UPDATE user_stats SET
+    viewed_but_not_started = array_remove(viewed_but_not_started, $2),
+    started_but_not_finished = CASE
+        WHEN $2 = ANY(COALESCE(started_but_not_finished, '{}') || COALESCE(finished, '{}'))
+        THEN started_but_not_finished
+        ELSE array_append(started_but_not_finished, $2)
+    END,
+    recent_watched = (array_prepend($2, array_remove(COALESCE(recent_watched, '{}'), $2)))[1:$4],
+    last_watched_at = GREATEST(last_watched_at, $3)
+WHERE email = $1
+"#;
+
+const RECORD_WATCH_FINISHED: &str = r#"
+UPDATE user_stats SET
+    viewed_but_not_started = array_remove(viewed_but_not_started, $2),
+    started_but_not_finished = array_remove(started_but_not_finished, $2),
+    finished = CASE
+        WHEN $2 = ANY(COALESCE(finished, '{}')) THEN finished
+        ELSE array_append(finished, $2)
+    END,
+    recent_watched = (array_prepend($2, array_remove(COALESCE(recent_watched, '{}'), $2)))[1:$4],
+    last_watched_at = GREATEST(last_watched_at, $3)
+WHERE email = $1
+"#;
+
+impl UserStatsService {
+    pub async fn upsert_user(&self, req: UpsertUserRequest) -> ServiceResult<RecordResponse> {
+        self.apply(Event::UpsertUser(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    pub async fn record_visit(&self, req: RecordVisitRequest) -> ServiceResult<RecordResponse> {
+        self.apply(Event::Visit(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    pub async fn record_view(&self, req: RecordWatchRequest) -> ServiceResult<RecordResponse> {
+        self.apply(Event::View(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    pub async fn record_watch_started(
+        &self,
+        req: RecordWatchRequest,
+    ) -> ServiceResult<RecordResponse> {
+        self.apply(Event::WatchStarted(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    pub async fn record_watch_finished(
+        &self,
+        req: RecordWatchRequest,
+    ) -> ServiceResult<RecordResponse> {
+        self.apply(Event::WatchFinished(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    pub async fn record_notification(
+        &self,
+        req: RecordNotificationRequest,
+    ) -> ServiceResult<RecordResponse> {
+        self.apply(Event::Notification(req)).await?;
+        Ok(Response::new(RecordResponse {}))
+    }
+
+    /// Apply the events in order. Invalid events and events of unknown users are counted as
+    /// rejected, while database failures abort the ingestion.
+    pub async fn ingest(
+        &self,
+        mut events: impl Stream<Item = Result<ActivityEvent, Status>> + Unpin,
+    ) -> ServiceResult<IngestResponse> {
+        let mut resp = IngestResponse::default();
+        while let Some(event) = events.next().await {
+            let ret = match event?.event {
+                Some(event) => self.apply(event).await,
+                None => Err(Status::invalid_argument("Event is empty")),
+            };
+
+            match ret {
+                Ok(()) => resp.accepted += 1,
+                Err(e) if e.code() == Code::Internal => return Err(e),
+                Err(e) => {
+                    warn!("Rejected event: {}", e.message());
+                    resp.rejected += 1;
+                }
+            }
+        }
+
+        Ok(Response::new(resp))
+    }
+
+    async fn apply(&self, event: Event) -> Result<(), Status> {
+        let pool = &self.inner.pool;
+        let (email, ret) = match event {
+            Event::UpsertUser(req) => {
+                check_email(&req.email)?;
+                if req.name.is_empty() || req.name.len() > MAX_NAME_LEN {
+                    return Err(Status::invalid_argument(format!(
+                        "Name must be 1 to {MAX_NAME_LEN} bytes long"
+                    )));
+                }
+                if req.phone.len() > MAX_PHONE_LEN {
+                    return Err(Status::invalid_argument(format!(
+                        "Phone must be at most {MAX_PHONE_LEN} bytes long"
+                    )));
+                }
+                if req.device_id.len() > MAX_DEVICE_ID_LEN {
+                    return Err(Status::invalid_argument(format!(
+                        "Device id must be at most {MAX_DEVICE_ID_LEN} bytes long"
+                    )));
+                }
+                if req.locale.len() > MAX_LOCALE_LEN {
+                    return Err(Status::invalid_argument(format!(
+                        "Locale must be at most {MAX_LOCALE_LEN} bytes long"
+                    )));
+                }
+                let ret = sqlx::query(UPSERT_USER)
+                    .bind(&req.email)
+                    .bind(&req.name)
+                    .bind(req.gender().as_sql())
+                    .bind(&req.phone)
+                    .bind(&req.device_id)
+                    .bind(&req.locale)
+                    .execute(pool)
+                    .await;
+                (req.email, ret)
+            }
+            Event::Visit(req) => {
+                check_email(&req.email)?;
+                let ret = sqlx::query(RECORD_VISIT)
+                    .bind(&req.email)
+                    .bind(event_time(req.at.as_ref())?)
+                    .execute(pool)
+                    .await;
+                (req.email, ret)
+            }
+            Event::View(req) => {
+                let (content_id, _) = check_watch(&req)?;
+                let ret = sqlx::query(RECORD_VIEW)
+                    .bind(&req.email)
+                    .bind(content_id)
+                    .execute(pool)
+                    .await;
+                (req.email, ret)
+            }
+            Event::WatchStarted(req) => {
+                let ret = record_watch(self, RECORD_WATCH_STARTED, &req).await?;
+                (req.email, ret)
+            }
+            Event::WatchFinished(req) => {
+                let ret = record_watch(self, RECORD_WATCH_FINISHED, &req).await?;
+                (req.email, ret)
+            }
+            Event::Notification(req) => {
+                check_email(&req.email)?;
+                let column = match req.channel() {
+                    NotificationChannel::Email => "last_email_notification",
+                    NotificationChannel::Sms => "last_sms_notification",
+                    NotificationChannel::InApp => "last_in_app_notification",
+                    NotificationChannel::Unspecified => {
+                        return Err(Status::invalid_argument(
+                            "Notification channel must be specified",
+                        ))
+                    }
+                };
+                let sql = format!(
+                    "UPDATE user_stats SET {column} = GREATEST({column}, $2) WHERE email = $1"
+                );
+                let ret = sqlx::query(&sql)
+                    .bind(&req.email)
+                    .bind(event_time(req.at.as_ref())?)
+                    .execute(pool)
+                    .await;
+                (req.email, ret)
+            }
+        };
+
+        let ret = ret.map_err(|e| {
+            warn!("Failed to record event of {}: {:?}", email, e);
+            Status::internal("Failed to record event")
+        })?;
+        if ret.rows_affected() == 0 {
+            return Err(Status::not_found(format!("User {email} not found")));
+        }
+
+        Ok(())
+    }
+}
+
+async fn record_watch(
+    svc: &UserStatsService,
+    sql: &'static str,
+    req: &RecordWatchRequest,
+) -> Result<Result<PgQueryResult, sqlx::Error>, Status> {
+    let (content_id, at) = check_watch(req)?;
+    Ok(sqlx::query(sql)
+        .bind(&req.email)
+        .bind(content_id)
+        .bind(at)
+        .bind(RECENT_WATCHED_LIMIT)
+        .execute(&svc.inner.pool)
+        .await)
+}
+
+fn check_email(email: &str) -> Result<(), Status> {
+    if email.is_empty() || email.len() > MAX_EMAIL_LEN {
+        return Err(Status::invalid_argument(format!(
+            "Email must be 1 to {MAX_EMAIL_LEN} bytes long"
+        )));
+    }
+    Ok(())
+}
+
+/// ids are stored as `int`, so they must fit in an i32
+fn check_watch(req: &RecordWatchRequest) -> Result<(i32, DateTime<Utc>), Status> {
+    check_email(&req.email)?;
+    let content_id = i32::try_from(req.content_id)
+        .map_err(|_| Status::invalid_argument(format!("Invalid content id: {}", req.content_id)))?;
+    Ok((content_id, event_time(req.at.as_ref())?))
+}
+
+fn event_time(at: Option<&Timestamp>) -> Result<DateTime<Utc>, Status> {
+    Ok(at.map(ts_to_utc).transpose()?.unwrap_or_else(Utc::now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::query::to_ts;
+    use crate::pb::{
+        Filter, Gender, QueryRequestBuilder, RecordNotificationRequestBuilder,
+        RecordVisitRequestBuilder, RecordWatchRequestBuilder, UpsertUserRequestBuilder, User,
+    };
+    use anyhow::Result;
+    use chrono::TimeZone;
+    use futures::stream;
+
+    type Ids = Option<Vec<i32>>;
+
+    const EMAIL: &str = "new.user@acme.org";
+
+    #[tokio::test]
+    async fn watch_events_should_move_ids_between_arrays() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        upsert(&svc, "New User", Gender::Unspecified).await?;
+
+        svc.record_view(watch(1)?).await?;
+        svc.record_view(watch(2)?).await?;
+        svc.record_watch_started(watch(1)?).await?;
+        assert_eq!(
+            arrays(&svc, EMAIL).await?,
+            (Some(vec![1]), Some(vec![2]), Some(vec![1]), None)
+        );
+
+        svc.record_watch_finished(watch(1)?).await?;
+        svc.record_watch_finished(watch(3)?).await?;
+        // viewing or starting a finished content changes nothing but recent_watched
+        svc.record_view(watch(3)?).await?;
+        svc.record_watch_started(watch(1)?).await?;
+        assert_eq!(
+            arrays(&svc, EMAIL).await?,
+            (
+                Some(vec![1, 3]),
+                Some(vec![2]),
+                Some(vec![]),
+                Some(vec![1, 3])
+            )
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watch_finished_should_remove_id_from_started() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let email = "adolph.02ts3f95@example.org";
+        let req = RecordWatchRequestBuilder::default()
+            .email(email)
+            .content_id(307917u32)
+            .build()?;
+        svc.record_watch_finished(req).await?;
+
+        let (recent, _, started, finished) = arrays(&svc, email).await?;
+        let started = started.unwrap();
+        assert_eq!(started.len(), 10);
+        assert!(!started.contains(&307917));
+        assert!(finished.unwrap().contains(&307917));
+        assert_eq!(recent.unwrap()[0], 307917);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upsert_user_should_keep_unspecified_gender() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        upsert(&svc, "New User", Gender::Unspecified).await?;
+        assert_eq!(
+            gender(&svc).await?,
+            ("New User".to_string(), "unknown".into())
+        );
+
+        upsert(&svc, "New User", Gender::Female).await?;
+        upsert(&svc, "Renamed", Gender::Unspecified).await?;
+        assert_eq!(
+            gender(&svc).await?,
+            ("Renamed".to_string(), "female".into())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upsert_user_should_keep_empty_contacts() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let req = UpsertUserRequestBuilder::default()
+            .email(EMAIL)
+            .name("New User")
+            .phone("+1 555 0100")
+            .locale("zh-TW")
+            .build()?;
+        svc.upsert_user(req).await?;
+        let req = UpsertUserRequestBuilder::default()
+            .email(EMAIL)
+            .name("New User")
+            .device_id("device-1")
+            .build()?;
+        svc.upsert_user(req).await?;
+
+        let query = QueryRequestBuilder::default()
+            .filter(Filter::not(Filter::null("phone")))
+            .build()?;
+        let users: Vec<User> = svc
+            .query(query)
+            .await?
+            .into_inner()
+            .map(|u| u.unwrap())
+            .collect()
+            .await;
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].phone, "+1 555 0100");
+        assert_eq!(users[0].device_id, "device-1");
+        assert_eq!(users[0].locale, "zh-TW");
+
+        let req = UpsertUserRequestBuilder::default()
+            .email(EMAIL)
+            .name("New User")
+            .phone("0".repeat(MAX_PHONE_LEN + 1))
+            .build()?;
+        let err = svc.upsert_user(req).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn visit_should_not_move_back_in_time() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        upsert(&svc, "New User", Gender::Unspecified).await?;
+
+        let d1 = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let d2 = Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap();
+        for dt in [d1, d2] {
+            let req = RecordVisitRequestBuilder::default()
+                .email(EMAIL)
+                .at(to_ts(dt))
+                .build()?;
+            svc.record_visit(req).await?;
+        }
+
+        let (visited,): (DateTime<Utc>,) =
+            sqlx::query_as("SELECT last_visited_at FROM user_stats WHERE email = $1")
+                .bind(EMAIL)
+                .fetch_one(&svc.inner.pool)
+                .await?;
+        assert_eq!(visited, d1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn events_of_unknown_users_should_be_rejected() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let err = svc.record_watch_started(watch(1)?).await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+
+        let events = [
+            Event::View(watch(1)?),
+            Event::UpsertUser(UpsertUserRequest {
+                email: EMAIL.to_string(),
+                name: "New User".to_string(),
+                ..Default::default()
+            }),
+            Event::View(watch(1)?),
+            Event::WatchStarted(watch(u32::MAX)?),
+        ];
+        let events = events
+            .into_iter()
+            .map(|e| Ok(ActivityEvent { event: Some(e) }))
+            .chain([Ok(ActivityEvent::default())]);
+        let resp = svc.ingest(stream::iter(events)).await?.into_inner();
+        assert_eq!(resp.accepted, 2);
+        assert_eq!(resp.rejected, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn notification_should_update_its_channel_column() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        upsert(&svc, "New User", Gender::Unspecified).await?;
+
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let req = RecordNotificationRequestBuilder::default()
+            .email(EMAIL)
+            .channel(NotificationChannel::Sms as i32)
+            .at(to_ts(at))
+            .build()?;
+        svc.record_notification(req).await?;
+
+        let ret: (Option<DateTime<Utc>>, Option<DateTime<Utc>>) = sqlx::query_as(
+            "SELECT last_email_notification, last_sms_notification FROM user_stats \
+            WHERE email = $1",
+        )
+        .bind(EMAIL)
+        .fetch_one(&svc.inner.pool)
+        .await?;
+        assert_eq!(ret, (None, Some(at)));
+
+        let req = RecordNotificationRequestBuilder::default()
+            .email(EMAIL)
+            .build()?;
+        let err = svc.record_notification(req).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    fn watch(content_id: u32) -> Result<RecordWatchRequest> {
+        Ok(RecordWatchRequestBuilder::default()
+            .email(EMAIL)
+            .content_id(content_id)
+            .build()?)
+    }
+
+    async fn upsert(svc: &UserStatsService, name: &str, gender: Gender) -> Result<()> {
+        let req = UpsertUserRequestBuilder::default()
+            .email(EMAIL)
+            .name(name)
+            .gender(gender as i32)
+            .build()?;
+        svc.upsert_user(req).await?;
+        Ok(())
+    }
+
+    async fn gender(svc: &UserStatsService) -> Result<(String, String)> {
+        let ret = sqlx::query_as("SELECT name, gender::text FROM user_stats WHERE email = $1")
+            .bind(EMAIL)
+            .fetch_one(&svc.inner.pool)
+            .await?;
+        Ok(ret)
+    }
+
+    /// recent_watched, viewed_but_not_started, started_but_not_finished, finished
+    async fn arrays(svc: &UserStatsService, email: &str) -> Result<(Ids, Ids, Ids, Ids)> {
+        let ret = sqlx::query_as(
+            "SELECT recent_watched, viewed_but_not_started, started_but_not_finished, finished \
+            FROM user_stats WHERE email = $1",
+        )
+        .bind(email)
+        .fetch_one(&svc.inner.pool)
+        .await?;
+        Ok(ret)
+    }
+}
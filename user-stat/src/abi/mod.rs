@@ -1,148 +1,237 @@
-use crate::pb::{QueryRequestBuilder, TimeQuery};
 use crate::{
-    pb::{QueryRequest, RawQueryRequest, User},
+    pb::{
+        CountResponse, Gender, QueryPageRequest, QueryPageResponse, QueryRequest, RawQueryRequest,
+        User,
+    },
     ResponseStream, ServiceResult, UserStatsService,
 };
-use chrono::{DateTime, TimeZone, Utc};
-use futures::stream;
-use itertools::Itertools as _;
-use prost_types::Timestamp;
-use std::fmt;
+use futures::{Stream, StreamExt as _, TryStreamExt as _};
+use raw_query::STATEMENT_TIMEOUT_MS;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Row};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
-use tracing::info;
+use tracing::{info, warn};
+
+mod filter;
+mod ingest;
+mod page;
+mod query;
+mod raw_query;
+
+const CHANNEL_SIZE: usize = 1024;
+
+/// columns a query returns, must match what `User::from_row` reads
+pub(crate) const USER_COLUMNS: [&str; 10] = [
+    "email",
+    "name",
+    "started_but_not_finished",
+    "phone",
+    "device_id",
+    "gender",
+    "recent_watched",
+    "viewed_but_not_started",
+    "finished",
+    "locale",
+];
+
+/// postgres error code for a statement cancelled by statement_timeout
+const QUERY_CANCELED: &str = "57014";
 
 impl UserStatsService {
     pub async fn query(&self, query: QueryRequest) -> ServiceResult<ResponseStream> {
-        let sql = query.to_string();
+        let mut qb = query.to_query_builder()?;
+        let sql = qb.sql().to_string();
+        info!("Generated SQL: {}", sql);
+
+        let pool = self.inner.pool.clone();
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let rows = qb.build_query_as::<User>().fetch(&pool);
+            forward(rows, tx, |e| {
+                warn!("Failed to fetch data with query {}: {:?}", sql, e);
+                Status::internal("Failed to fetch data")
+            })
+            .await;
+        });
 
-        self.raw_query(RawQueryRequest { query: sql }).await
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
     }
 
-    pub async fn raw_query(&self, req: RawQueryRequest) -> ServiceResult<ResponseStream> {
-        // TODO: query must only return email and name, so we should use sql parser to parse the query
-        let Ok(ret) = sqlx::query_as::<_, User>(&req.query)
-            .fetch_all(&self.inner.pool)
+    pub async fn count(&self, query: QueryRequest) -> ServiceResult<CountResponse> {
+        let mut qb = query.to_count_builder()?;
+        let count: i64 = qb
+            .build_query_scalar()
+            .fetch_one(&self.inner.pool)
             .await
-        else {
-            return Err(Status::internal(format!(
-                "Failed to fetch data with query: {}",
-                req.query
-            )));
-        };
+            .map_err(|e| {
+                warn!("Failed to count with query {}: {:?}", qb.sql(), e);
+                Status::internal("Failed to count users")
+            })?;
 
-        Ok(Response::new(Box::pin(stream::iter(
-            ret.into_iter().map(Ok),
-        ))))
+        Ok(Response::new(CountResponse { count: count as _ }))
     }
-}
 
-impl fmt::Display for QueryRequest {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // generate sql based on query
-        let mut sql = "SELECT email, name FROM user_stats WHERE ".to_string();
+    pub async fn query_page(&self, req: QueryPageRequest) -> ServiceResult<QueryPageResponse> {
+        let limit = req.limit();
+        let query = req.query.clone().unwrap_or_default();
+        // fetch one more row to know whether there is a next page
+        let mut qb = query.to_page_builder(req.after()?, limit + 1)?;
 
-        let time_conditions = self
-            .timestamps
-            .iter()
-            .map(|(k, v)| timestamp_query(k, v.lower.as_ref(), v.upper.as_ref()))
-            .join(" AND ");
+        let mut users: Vec<User> = qb
+            .build_query_as()
+            .fetch(&self.inner.pool)
+            .try_collect()
+            .await
+            .map_err(|e| {
+                warn!("Failed to fetch page with query {}: {:?}", qb.sql(), e);
+                Status::internal("Failed to fetch data")
+            })?;
 
-        sql.push_str(&time_conditions);
+        let next_cursor = if users.len() > limit as usize {
+            users.truncate(limit as _);
+            users.last().map(|u| page::encode_cursor(&u.email))
+        } else {
+            None
+        };
 
-        let id_conditions = self
-            .ids
-            .iter()
-            .map(|(k, v)| ids_query(k, &v.ids))
-            .join(" AND ");
+        Ok(Response::new(QueryPageResponse {
+            users,
+            next_cursor: next_cursor.unwrap_or_default(),
+        }))
+    }
+
+    pub async fn raw_query(&self, req: RawQueryRequest) -> ServiceResult<ResponseStream> {
+        let sql = raw_query::sanitize(&req.query)?;
+        info!("Sanitized raw query: {}", sql);
 
-        if !id_conditions.is_empty() {
-            sql.push_str(" AND ");
-            sql.push_str(&id_conditions);
+        // run the query in a read-only transaction so that it could not modify anything, and
+        // let postgres cancel it if it runs for too long
+        let mut db_tx = async {
+            let mut db_tx = self.inner.pool.begin().await?;
+            sqlx::query("SET TRANSACTION READ ONLY")
+                .execute(&mut *db_tx)
+                .await?;
+            sqlx::query(&format!(
+                "SET LOCAL statement_timeout = {STATEMENT_TIMEOUT_MS}"
+            ))
+            .execute(&mut *db_tx)
+            .await?;
+            Ok::<_, sqlx::Error>(db_tx)
         }
+        .await
+        .map_err(|e| raw_query_error(&sql, e))?;
 
-        info!("Generated SQL: {}", sql);
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let rows = sqlx::query_as::<_, User>(&sql).fetch(&mut *db_tx);
+            forward(rows, tx, |e| raw_query_error(&sql, e)).await;
+            if let Err(e) = db_tx.rollback().await {
+                warn!("Failed to rollback raw query transaction: {:?}", e);
+            }
+        });
 
-        write!(f, "{}", sql)
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
     }
 }
 
-impl QueryRequest {
-    pub fn new_with_dt(name: &str, lower: DateTime<Utc>, upper: DateTime<Utc>) -> Self {
-        let ts = Timestamp {
-            seconds: lower.timestamp(),
-            nanos: 0,
-        };
-        let ts1 = Timestamp {
-            seconds: upper.timestamp(),
-            nanos: 0,
-        };
-        let tq = TimeQuery {
-            lower: Some(ts),
-            upper: Some(ts1),
-        };
-
-        QueryRequestBuilder::default()
-            .timestamp((name.to_string(), tq))
-            .build()
-            .expect("Failed to build query request")
+/// Forward rows to the response stream as they come in. The bounded channel applies
+/// backpressure to the database cursor, and dropping the rows stream once the client has gone
+/// cancels the query.
+async fn forward(
+    mut rows: impl Stream<Item = Result<User, sqlx::Error>> + Unpin,
+    tx: mpsc::Sender<Result<User, Status>>,
+    on_error: impl Fn(sqlx::Error) -> Status,
+) {
+    while let Some(row) = rows.next().await {
+        let row = row.map_err(&on_error);
+        let failed = row.is_err();
+        if tx.send(row).await.is_err() {
+            warn!("Client disconnected, stop fetching rows");
+            break;
+        }
+        if failed {
+            break;
+        }
     }
 }
 
-fn ids_query(name: &str, ids: &[u32]) -> String {
-    if ids.is_empty() {
-        return "TRUE".to_string();
+fn raw_query_error(sql: &str, e: sqlx::Error) -> Status {
+    warn!("Failed to fetch data with query {}: {:?}", sql, e);
+    match e {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(QUERY_CANCELED) => {
+            Status::deadline_exceeded(format!(
+                "Raw query took longer than {STATEMENT_TIMEOUT_MS}ms"
+            ))
+        }
+        sqlx::Error::Database(e) => {
+            Status::invalid_argument(format!("Invalid raw query: {}", e.message()))
+        }
+        _ => Status::internal(format!("Failed to fetch data with query: {sql}")),
     }
-
-    format!("array{:?} <@ {}", ids, name)
 }
 
-fn timestamp_query(name: &str, lower: Option<&Timestamp>, upper: Option<&Timestamp>) -> String {
-    if lower.is_none() && upper.is_none() {
-        return "TRUE".to_string();
+impl<'r> FromRow<'r, PgRow> for User {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(User {
+            email: row.try_get("email")?,
+            name: row.try_get("name")?,
+            started_but_not_finished: try_get_ids(row, "started_but_not_finished")?,
+            phone: try_get_text(row, "phone")?,
+            device_id: try_get_text(row, "device_id")?,
+            gender: try_get_gender(row)? as _,
+            recent_watched: try_get_ids(row, "recent_watched")?,
+            viewed_but_not_started: try_get_ids(row, "viewed_but_not_started")?,
+            finished: try_get_ids(row, "finished")?,
+            locale: try_get_text(row, "locale")?,
+        })
     }
+}
 
-    if lower.is_none() {
-        let upper = ts_to_utc(upper.unwrap());
-        return format!("{} <= '{}'", name, upper.to_rfc3339());
+/// gender is a nullable postgres enum, which may not be selected at all
+fn try_get_gender(row: &PgRow) -> Result<Gender, sqlx::Error> {
+    // the label of an enum value is sent as is, so it decodes as text
+    match row.try_get_unchecked::<Option<String>, _>("gender") {
+        Ok(gender) => Ok(gender
+            .as_deref()
+            .map_or(Gender::Unspecified, Gender::from_sql)),
+        Err(sqlx::Error::ColumnNotFound(_)) => Ok(Gender::Unspecified),
+        Err(e) => Err(e),
     }
+}
 
-    if upper.is_none() {
-        let lower = ts_to_utc(lower.unwrap());
-        return format!("{} >= '{}'", name, lower.to_rfc3339());
+/// contact and locale columns are nullable, and may not be selected at all
+fn try_get_text(row: &PgRow, name: &str) -> Result<String, sqlx::Error> {
+    match row.try_get::<Option<String>, _>(name) {
+        Ok(contact) => Ok(contact.unwrap_or_default()),
+        Err(sqlx::Error::ColumnNotFound(_)) => Ok(String::new()),
+        Err(e) => Err(e),
     }
-
-    format!(
-        "{} BETWEEN '{}' AND '{}'",
-        name,
-        ts_to_utc(lower.unwrap()).to_rfc3339(),
-        ts_to_utc(upper.unwrap()).to_rfc3339()
-    )
 }
 
-fn ts_to_utc(ts: &Timestamp) -> DateTime<Utc> {
-    Utc.timestamp_opt(ts.seconds, ts.nanos as _).unwrap()
+/// id array columns are nullable `int[]`, and may not be selected at all
+fn try_get_ids(row: &PgRow, name: &str) -> Result<Vec<u32>, sqlx::Error> {
+    match row.try_get::<Option<Vec<i32>>, _>(name) {
+        Ok(ids) => Ok(ids
+            .unwrap_or_default()
+            .into_iter()
+            .map(|id| id as _)
+            .collect()),
+        Err(sqlx::Error::ColumnNotFound(_)) => Ok(vec![]),
+        Err(e) => Err(e),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use stream::StreamExt as _;
+    use tonic::Code;
 
     use super::*;
-    use crate::pb::QueryRequestBuilder;
-    use crate::test_utils::{id, tq};
-
-    #[test]
-    fn query_request_to_string_should_work() {
-        let d1 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
-        let d2 = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
-        let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let sql = query.to_string();
-        assert_eq!(
-            sql,
-            "SELECT email, name FROM user_stats WHERE created_at BETWEEN '2024-01-01T00:00:00+00:00' AND '2024-01-02T00:00:00+00:00'"
-        );
-    }
+    use crate::pb::{IdMatch, QueryPageRequestBuilder, QueryRequestBuilder};
+    use crate::test_utils::{id, id_match, tq};
+    use chrono::{TimeZone, Utc};
 
     #[tokio::test]
     async fn test_raw_query_should_work() -> Result<()> {
@@ -162,6 +251,144 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_raw_query_should_return_unfinished_ids() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let query = "SELECT email, name, started_but_not_finished FROM user_stats \
+            WHERE email = 'adolph.02ts3f95@example.org'";
+        let ret = svc
+            .raw_query(RawQueryRequest {
+                query: query.to_string(),
+            })
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ret.len(), 1);
+        let user = ret[0].as_ref().unwrap();
+        assert_eq!(user.started_but_not_finished.len(), 11);
+        assert_eq!(user.started_but_not_finished[0], 307917);
+        // columns not selected are left empty
+        assert_eq!(user.gender(), Gender::Unspecified);
+        assert!(user.finished.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_query_should_stream_errors() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        // passes validation, but fails once postgres runs it
+        let query = "SELECT email, name FROM user_stats WHERE email::int > 0";
+        let ret = svc
+            .raw_query(RawQueryRequest {
+                query: query.to_string(),
+            })
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ret.len(), 1);
+        let err = ret[0].as_ref().unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_should_stream_all_rows() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let ret = svc
+            .query(QueryRequest::default())
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ret.len(), 116);
+        assert!(ret.iter().all(|u| u.is_ok()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_count_should_match_query() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let count = svc.count(QueryRequest::default()).await?.into_inner().count;
+        assert_eq!(count, 116);
+
+        let query = QueryRequestBuilder::default()
+            .id((
+                "finished".to_string(),
+                id_match(&[434213, 424777], IdMatch::Any),
+            ))
+            .build()?;
+        let count = svc.count(query).await?.into_inner().count;
+        assert_eq!(count, 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_page_should_walk_through_all_users() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+
+        let mut emails = Vec::new();
+        let mut cursor = String::new();
+        let mut pages = 0;
+        loop {
+            let req = QueryPageRequestBuilder::default()
+                .query(QueryRequest::default())
+                .page_size(50u32)
+                .cursor(cursor)
+                .build()?;
+            let page = svc.query_page(req).await?.into_inner();
+            pages += 1;
+            emails.extend(page.users.into_iter().map(|u| u.email));
+            if page.next_cursor.is_empty() {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(emails.len(), 116);
+        assert!(emails.windows(2).all(|w| w[0] < w[1]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_id_match_modes() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        // 434213 and 424777 are each finished by two different users
+        let cases = [
+            (&[434213][..], IdMatch::All, 2),
+            (&[434213, 424777], IdMatch::All, 0),
+            (&[480333, 480226], IdMatch::All, 1),
+            (&[434213, 424777], IdMatch::Any, 4),
+            (&[434213, 424777], IdMatch::None, 112),
+            (&[], IdMatch::Any, 116),
+        ];
+
+        for (ids, mode, expected) in cases {
+            let query = QueryRequestBuilder::default()
+                .id(("finished".to_string(), id_match(ids, mode)))
+                .build()?;
+            let ret = svc
+                .query(query)
+                .await?
+                .into_inner()
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(ret.len(), expected, "{ids:?} {mode:?}");
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_query_should_work() -> Result<()> {
         let (_tdb, svc) = UserStatsService::new_for_test().await?;
@@ -178,4 +405,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_query_should_bind_values() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let d1 = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+        let d2 = Utc.with_ymd_and_hms(2024, 1, 1, 8, 1, 0).unwrap();
+        let query = QueryRequest::new_with_dt("created_at", d1, d2);
+        let ret = svc
+            .query(query)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+
+        let users: Vec<_> = ret.into_iter().map(|u| u.unwrap()).collect();
+        let user = users
+            .iter()
+            .find(|u| u.email == "adolph.02ts3f95@example.org")
+            .expect("adolph should be registered at the time");
+        // the profile and watch history are returned along with the contacts
+        assert_ne!(user.gender(), Gender::Unspecified);
+        assert_eq!(user.started_but_not_finished.len(), 11);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,77 @@
+#![allow(clippy::result_large_err)]
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use tonic::Status;
+
+use crate::pb::QueryPageRequest;
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+impl QueryPageRequest {
+    /// page size with the default applied and capped at the max
+    pub(super) fn limit(&self) -> u32 {
+        match self.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        }
+    }
+
+    /// email of the last user of the previous page, if any
+    pub(super) fn after(&self) -> Result<Option<String>, Status> {
+        if self.cursor.is_empty() {
+            return Ok(None);
+        }
+        decode_cursor(&self.cursor).map(Some)
+    }
+}
+
+/// The cursor is opaque to clients, it is the email of the last user returned.
+pub(super) fn encode_cursor(email: &str) -> String {
+    URL_SAFE_NO_PAD.encode(email)
+}
+
+fn decode_cursor(cursor: &str) -> Result<String, Status> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|v| String::from_utf8(v).ok())
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid cursor: {cursor}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn cursor_should_round_trip() {
+        let email = "adolph.02ts3f95@example.org";
+        let req = QueryPageRequest {
+            cursor: encode_cursor(email),
+            ..Default::default()
+        };
+        assert_eq!(req.after().unwrap().as_deref(), Some(email));
+        assert_eq!(QueryPageRequest::default().after().unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_cursor_should_be_rejected() {
+        let req = QueryPageRequest {
+            cursor: "not a cursor!".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(req.after().unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn page_size_should_be_defaulted_and_capped() {
+        let size = |page_size| QueryPageRequest {
+            page_size,
+            ..Default::default()
+        };
+        assert_eq!(size(0).limit(), DEFAULT_PAGE_SIZE);
+        assert_eq!(size(10).limit(), 10);
+        assert_eq!(size(100000).limit(), MAX_PAGE_SIZE);
+    }
+}
@@ -0,0 +1,338 @@
+#![allow(clippy::result_large_err)]
+
+use chrono::{DateTime, TimeZone, Utc};
+use itertools::Itertools as _;
+use prost_types::Timestamp;
+use sqlx::{Postgres, QueryBuilder};
+use tonic::Status;
+
+use super::USER_COLUMNS;
+use crate::pb::{IdMatch, IdQuery, QueryRequest, QueryRequestBuilder, TimeQuery};
+
+pub(super) const TIMESTAMP_COLUMNS: [&str; 6] = [
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+];
+
+pub(super) const ID_COLUMNS: [&str; 4] = [
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+];
+
+pub(super) const CONTACT_COLUMNS: [&str; 2] = ["phone", "device_id"];
+
+impl QueryRequest {
+    pub fn new_with_dt(name: &str, lower: DateTime<Utc>, upper: DateTime<Utc>) -> Self {
+        let ts = Timestamp {
+            seconds: lower.timestamp(),
+            nanos: 0,
+        };
+        let ts1 = Timestamp {
+            seconds: upper.timestamp(),
+            nanos: 0,
+        };
+        let tq = TimeQuery {
+            lower: Some(ts),
+            upper: Some(ts1),
+        };
+
+        QueryRequestBuilder::default()
+            .timestamp((name.to_string(), tq))
+            .build()
+            .expect("Failed to build query request")
+    }
+
+    /// Build a parameterized query out of the request. Column names are checked against the
+    /// `user_stats` columns and all values are bound as parameters.
+    pub fn to_query_builder(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let (qb, _) = self.select(&USER_COLUMNS.join(", "))?;
+        Ok(qb)
+    }
+
+    /// Same conditions as `to_query_builder`, but only count the matching users.
+    pub fn to_count_builder(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let (qb, _) = self.select("count(*)")?;
+        Ok(qb)
+    }
+
+    /// Same conditions as `to_query_builder`, ordered by email and starting after the given
+    /// email. `limit` rows at most are returned.
+    pub fn to_page_builder(
+        &self,
+        after: Option<String>,
+        limit: u32,
+    ) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let (mut qb, mut conditions) = self.select(&USER_COLUMNS.join(", "))?;
+        if let Some(email) = after {
+            conditions.push(&mut qb);
+            qb.push("email > ").push_bind(email);
+        }
+        qb.push(" ORDER BY email LIMIT ").push_bind(limit as i64);
+        Ok(qb)
+    }
+
+    fn select(
+        &self,
+        columns: &str,
+    ) -> Result<(QueryBuilder<'static, Postgres>, Conditions), Status> {
+        let mut qb = QueryBuilder::new(format!("SELECT {columns} FROM user_stats"));
+        let mut conditions = Conditions::default();
+
+        // sort by name so that the generated sql is stable
+        for (name, tq) in self.timestamps.iter().sorted_by_key(|(k, _)| *k) {
+            let name = column(name, &TIMESTAMP_COLUMNS)?;
+            timestamp_query(&mut qb, &mut conditions, name, tq)?;
+        }
+
+        for (name, iq) in self.ids.iter().sorted_by_key(|(k, _)| *k) {
+            let name = column(name, &ID_COLUMNS)?;
+            ids_query(&mut qb, &mut conditions, name, iq);
+        }
+
+        if let Some(filter) = self.filter.as_ref().filter(|f| f.expr.is_some()) {
+            conditions.push(&mut qb);
+            filter.push_to(&mut qb)?;
+        }
+
+        Ok((qb, conditions))
+    }
+}
+
+/// Keeps track of whether a condition is the first one in the WHERE clause.
+#[derive(Default)]
+struct Conditions(bool);
+
+impl Conditions {
+    fn push(&mut self, qb: &mut QueryBuilder<'static, Postgres>) {
+        qb.push(if self.0 { " AND " } else { " WHERE " });
+        self.0 = true;
+    }
+}
+
+pub(super) fn column<'a>(name: &str, allowed: &[&'a str]) -> Result<&'a str, Status> {
+    allowed
+        .iter()
+        .find(|c| **c == name)
+        .copied()
+        .ok_or_else(|| Status::invalid_argument(format!("Unknown column: {name}")))
+}
+
+fn ids_query(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    conditions: &mut Conditions,
+    name: &str,
+    iq: &IdQuery,
+) {
+    if iq.ids.is_empty() {
+        return;
+    }
+
+    conditions.push(qb);
+    match iq.mode() {
+        IdMatch::Unspecified | IdMatch::All => push_ids(qb, name, " @> ", &iq.ids),
+        IdMatch::Any => push_ids(qb, name, " && ", &iq.ids),
+        IdMatch::None => {
+            // `IS NOT TRUE` keeps the users whose column is NULL
+            qb.push("(");
+            push_ids(qb, name, " && ", &iq.ids);
+            qb.push(") IS NOT TRUE");
+        }
+    }
+}
+
+/// push `name <op> $n` with the column on the left, so that the GIN index on it could be used
+pub(super) fn push_ids(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    name: &str,
+    op: &str,
+    ids: &[u32],
+) {
+    let ids: Vec<i32> = ids.iter().map(|id| *id as _).collect();
+    qb.push(name).push(op).push_bind(ids);
+}
+
+fn timestamp_query(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    conditions: &mut Conditions,
+    name: &str,
+    tq: &TimeQuery,
+) -> Result<(), Status> {
+    if tq.lower.is_none() && tq.upper.is_none() {
+        return Ok(());
+    }
+
+    conditions.push(qb);
+    push_time_range(qb, name, tq.lower.as_ref(), tq.upper.as_ref())
+}
+
+/// push `name` compared with the bounds, or TRUE if there are no bounds
+pub(super) fn push_time_range(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    name: &str,
+    lower: Option<&Timestamp>,
+    upper: Option<&Timestamp>,
+) -> Result<(), Status> {
+    let lower = lower.map(ts_to_utc).transpose()?;
+    let upper = upper.map(ts_to_utc).transpose()?;
+
+    match (lower, upper) {
+        (None, None) => {
+            qb.push("TRUE");
+        }
+        (None, Some(upper)) => {
+            qb.push(name).push(" <= ").push_bind(upper);
+        }
+        (Some(lower), None) => {
+            qb.push(name).push(" >= ").push_bind(lower);
+        }
+        (Some(lower), Some(upper)) => {
+            qb.push(name)
+                .push(" BETWEEN ")
+                .push_bind(lower)
+                .push(" AND ")
+                .push_bind(upper);
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn to_ts(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as _,
+    }
+}
+
+pub(super) fn ts_to_utc(ts: &Timestamp) -> Result<DateTime<Utc>, Status> {
+    Utc.timestamp_opt(ts.seconds, ts.nanos as _)
+        .single()
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid timestamp: {ts}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{id, id_match, tq};
+    use anyhow::Result;
+    use tonic::Code;
+
+    #[test]
+    fn query_request_to_query_builder_should_work() -> Result<()> {
+        let d1 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let d2 = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let query = QueryRequest::new_with_dt("created_at", d1, d2);
+        let qb = query.to_query_builder()?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats WHERE created_at BETWEEN $1 AND $2"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_request_with_multiple_conditions_should_bind_all_values() -> Result<()> {
+        let query = QueryRequestBuilder::default()
+            .timestamp(("last_visited_at".to_string(), tq(Some(30), None)))
+            .timestamp(("created_at".to_string(), tq(None, Some(120))))
+            .id(("finished".to_string(), id(&[1, 2])))
+            .id(("recent_watched".to_string(), id(&[])))
+            .build()?;
+        let qb = query.to_query_builder()?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats \
+            WHERE created_at <= $1 AND last_visited_at >= $2 AND finished @> $3"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn id_query_should_respect_match_mode() -> Result<()> {
+        let cases = [
+            (IdMatch::Unspecified, "finished @> $1"),
+            (IdMatch::All, "finished @> $1"),
+            (IdMatch::Any, "finished && $1"),
+            (IdMatch::None, "(finished && $1) IS NOT TRUE"),
+        ];
+
+        for (mode, expected) in cases {
+            let query = QueryRequestBuilder::default()
+                .id(("finished".to_string(), id_match(&[1, 2], mode)))
+                .build()?;
+            let qb = query.to_query_builder()?;
+            assert_eq!(qb.sql().split(" WHERE ").nth(1), Some(expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn count_and_page_builders_should_share_conditions() -> Result<()> {
+        let query = QueryRequestBuilder::default()
+            .timestamp(("created_at".to_string(), tq(None, Some(120))))
+            .build()?;
+
+        let qb = query.to_count_builder()?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT count(*) FROM user_stats WHERE created_at <= $1"
+        );
+
+        let qb = query.to_page_builder(None, 10)?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats \
+            WHERE created_at <= $1 ORDER BY email LIMIT $2"
+        );
+
+        let qb = QueryRequest::default().to_page_builder(Some("a@b.c".to_string()), 10)?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats \
+            WHERE email > $1 ORDER BY email LIMIT $2"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_request_without_conditions_should_select_all() -> Result<()> {
+        let qb = QueryRequest::default().to_query_builder()?;
+        assert_eq!(
+            qb.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_request_with_unknown_column_should_be_rejected() -> Result<()> {
+        let query = QueryRequestBuilder::default()
+            .timestamp((
+                "created_at > now(); DROP TABLE user_stats; --".to_string(),
+                tq(Some(30), None),
+            ))
+            .build()?;
+        let err = query.to_query_builder().err().unwrap();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        // id columns and timestamp columns are not interchangeable
+        let query = QueryRequestBuilder::default()
+            .id(("created_at".to_string(), id(&[1])))
+            .build()?;
+        let err = query.to_query_builder().err().unwrap();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+}
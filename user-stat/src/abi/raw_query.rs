@@ -0,0 +1,317 @@
+use std::fmt;
+use std::ops::ControlFlow;
+
+use sqlparser::ast::{
+    visit_expressions, visit_relations, Expr, Ident, ObjectName, Query, SelectItem, SetExpr,
+    Statement, TableFactor, Value,
+};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use tonic::Status;
+
+use super::USER_COLUMNS;
+
+/// max number of rows a raw query could return
+pub(crate) const MAX_LIMIT: u64 = 10000;
+/// raw queries are cancelled by postgres if they run longer than this
+pub(crate) const STATEMENT_TIMEOUT_MS: u64 = 5000;
+
+const TABLE: &str = "user_stats";
+
+/// functions which are allowed to be called in a raw query
+const ALLOWED_FUNCTIONS: [&str; 9] = [
+    "array_length",
+    "cardinality",
+    "coalesce",
+    "count",
+    "date_trunc",
+    "length",
+    "lower",
+    "now",
+    "upper",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RawQueryError {
+    Parse(String),
+    StatementCount(usize),
+    NotSelect,
+    SetOperation,
+    With,
+    Locking,
+    Into,
+    NoTable,
+    Table(String),
+    Join,
+    TableFunction,
+    Projection(String),
+    MissingColumn(&'static str),
+    Function(String),
+    Limit(String),
+    Fetch,
+}
+
+impl fmt::Display for RawQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse query: {e}"),
+            Self::StatementCount(n) => write!(f, "expected exactly one statement, got {n}"),
+            Self::NotSelect => write!(f, "only SELECT statements are allowed"),
+            Self::SetOperation => write!(f, "UNION, INTERSECT and EXCEPT are not allowed"),
+            Self::With => write!(f, "WITH clauses are not allowed"),
+            Self::Locking => write!(
+                f,
+                "locking clauses (FOR UPDATE / FOR SHARE) are not allowed"
+            ),
+            Self::Into => write!(f, "SELECT INTO is not allowed"),
+            Self::NoTable => write!(f, "query must select from {TABLE}"),
+            Self::Table(name) => write!(f, "only {TABLE} could be queried, got {name}"),
+            Self::Join => write!(f, "joins are not allowed"),
+            Self::TableFunction => write!(f, "table functions are not allowed"),
+            Self::Projection(item) => write!(
+                f,
+                "only columns {} could be selected, got {item}",
+                USER_COLUMNS.join(", ")
+            ),
+            Self::MissingColumn(name) => write!(f, "column {name} must be selected"),
+            Self::Function(name) => write!(f, "function {name} is not allowed"),
+            Self::Limit(limit) => write!(f, "LIMIT must be a number, got {limit}"),
+            Self::Fetch => write!(f, "FETCH is not allowed, use LIMIT instead"),
+        }
+    }
+}
+
+impl From<RawQueryError> for Status {
+    fn from(e: RawQueryError) -> Self {
+        Status::invalid_argument(format!("Invalid raw query: {e}"))
+    }
+}
+
+/// Parse the raw query and make sure it is a single read-only SELECT on user_stats which
+/// returns the user columns. The returned sql has `*` expanded and its LIMIT capped.
+pub(crate) fn sanitize(sql: &str) -> Result<String, RawQueryError> {
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+        .map_err(|e| RawQueryError::Parse(e.to_string()))?;
+    if statements.len() != 1 {
+        return Err(RawQueryError::StatementCount(statements.len()));
+    }
+
+    let mut statement = statements.remove(0);
+    let Statement::Query(query) = &mut statement else {
+        return Err(RawQueryError::NotSelect);
+    };
+    check_query(query)?;
+
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return Err(RawQueryError::SetOperation);
+    };
+    if select.into.is_some() {
+        return Err(RawQueryError::Into);
+    }
+    match select.from.as_slice() {
+        [] => return Err(RawQueryError::NoTable),
+        [table] if table.joins.is_empty() => match &table.relation {
+            TableFactor::Table { args: None, .. } => {}
+            TableFactor::Table { .. } => return Err(RawQueryError::TableFunction),
+            relation => return Err(RawQueryError::Table(relation.to_string())),
+        },
+        _ => return Err(RawQueryError::Join),
+    }
+    select.projection = projection(&select.projection)?;
+
+    // subqueries are fine as long as they only read from user_stats
+    if let ControlFlow::Break(e) = visit_relations(&statement, check_relation) {
+        return Err(e);
+    }
+    if let ControlFlow::Break(e) = visit_expressions(&statement, check_expr) {
+        return Err(e);
+    }
+
+    let Statement::Query(query) = &mut statement else {
+        unreachable!()
+    };
+    query.limit = Some(limit(query.limit.take())?);
+
+    Ok(statement.to_string())
+}
+
+fn check_query(query: &Query) -> Result<(), RawQueryError> {
+    if query.with.is_some() {
+        return Err(RawQueryError::With);
+    }
+    if !query.locks.is_empty() {
+        return Err(RawQueryError::Locking);
+    }
+    if query.fetch.is_some() {
+        return Err(RawQueryError::Fetch);
+    }
+    Ok(())
+}
+
+fn projection(items: &[SelectItem]) -> Result<Vec<SelectItem>, RawQueryError> {
+    if let [SelectItem::Wildcard(_)] = items {
+        return Ok(USER_COLUMNS
+            .iter()
+            .map(|c| SelectItem::UnnamedExpr(Expr::Identifier(Ident::new(*c))))
+            .collect());
+    }
+
+    let mut columns = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            SelectItem::UnnamedExpr(Expr::Identifier(ident))
+                if USER_COLUMNS.contains(&ident.value.as_str()) =>
+            {
+                columns.push(ident.value.as_str())
+            }
+            item => return Err(RawQueryError::Projection(item.to_string())),
+        }
+    }
+
+    for name in ["email", "name"] {
+        if !columns.contains(&name) {
+            return Err(RawQueryError::MissingColumn(name));
+        }
+    }
+
+    Ok(items.to_vec())
+}
+
+fn limit(limit: Option<Expr>) -> Result<Expr, RawQueryError> {
+    let n = match limit {
+        None => MAX_LIMIT,
+        Some(Expr::Value(Value::Number(n, _))) => n
+            .parse::<u64>()
+            .map_err(|_| RawQueryError::Limit(n.clone()))?
+            .min(MAX_LIMIT),
+        Some(expr) => return Err(RawQueryError::Limit(expr.to_string())),
+    };
+
+    Ok(Expr::Value(Value::Number(n.to_string(), false)))
+}
+
+fn check_relation(name: &ObjectName) -> ControlFlow<RawQueryError> {
+    let table = match name.0.as_slice() {
+        [table] => table,
+        [schema, table] if schema.value == "public" => table,
+        _ => return ControlFlow::Break(RawQueryError::Table(name.to_string())),
+    };
+
+    if table.value != TABLE {
+        return ControlFlow::Break(RawQueryError::Table(name.to_string()));
+    }
+
+    ControlFlow::Continue(())
+}
+
+fn check_expr(expr: &Expr) -> ControlFlow<RawQueryError> {
+    if let Expr::Function(f) = expr {
+        let name = f.name.to_string().to_lowercase();
+        if !ALLOWED_FUNCTIONS.contains(&name.as_str()) {
+            return ControlFlow::Break(RawQueryError::Function(f.name.to_string()));
+        }
+    }
+
+    ControlFlow::Continue(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_should_expand_wildcard_and_add_limit() {
+        let sql = sanitize("select * from user_stats where created_at > '2024-05-01'").unwrap();
+        assert_eq!(
+            sql,
+            "SELECT email, name, started_but_not_finished, phone, device_id, gender, recent_watched, viewed_but_not_started, finished, locale FROM user_stats WHERE created_at > '2024-05-01' LIMIT 10000"
+        );
+    }
+
+    #[test]
+    fn sanitize_should_cap_limit() {
+        let sql = sanitize("SELECT email, name FROM user_stats LIMIT 5").unwrap();
+        assert_eq!(sql, "SELECT email, name FROM user_stats LIMIT 5");
+
+        let sql = sanitize("SELECT email, name FROM user_stats LIMIT 1000000").unwrap();
+        assert_eq!(sql, "SELECT email, name FROM user_stats LIMIT 10000");
+    }
+
+    #[test]
+    fn sanitize_should_allow_subqueries_on_user_stats() {
+        let sql = "SELECT email, name FROM user_stats WHERE email IN \
+            (SELECT email FROM user_stats WHERE cardinality(finished) > 3)";
+        assert!(sanitize(sql).is_ok());
+    }
+
+    #[test]
+    fn sanitize_should_reject_invalid_queries() {
+        let cases = [
+            ("SELEC email FROM user_stats", None),
+            (
+                "SELECT email, name FROM user_stats; DROP TABLE user_stats",
+                Some(RawQueryError::StatementCount(2)),
+            ),
+            ("DELETE FROM user_stats", Some(RawQueryError::NotSelect)),
+            (
+                "UPDATE user_stats SET name = 'a'",
+                Some(RawQueryError::NotSelect),
+            ),
+            (
+                "SELECT email, name FROM user_stats UNION SELECT usename, passwd FROM pg_shadow",
+                Some(RawQueryError::SetOperation),
+            ),
+            (
+                "WITH t AS (SELECT * FROM user_stats) SELECT email, name FROM t",
+                Some(RawQueryError::With),
+            ),
+            (
+                "SELECT email, name FROM user_stats FOR UPDATE",
+                Some(RawQueryError::Locking),
+            ),
+            (
+                "SELECT email, name INTO copy FROM user_stats",
+                Some(RawQueryError::Into),
+            ),
+            ("SELECT 1", Some(RawQueryError::NoTable)),
+            (
+                "SELECT email, name FROM pg_user",
+                Some(RawQueryError::Table("pg_user".to_string())),
+            ),
+            (
+                "SELECT email, name FROM user_stats u JOIN user_stats v ON u.email = v.email",
+                Some(RawQueryError::Join),
+            ),
+            (
+                "SELECT email, name FROM user_stats WHERE email IN (SELECT usename FROM pg_user)",
+                Some(RawQueryError::Table("pg_user".to_string())),
+            ),
+            (
+                "SELECT email, name, last_email_notification FROM user_stats",
+                Some(RawQueryError::Projection(
+                    "last_email_notification".to_string(),
+                )),
+            ),
+            (
+                "SELECT email FROM user_stats",
+                Some(RawQueryError::MissingColumn("name")),
+            ),
+            (
+                "SELECT email, name FROM user_stats WHERE pg_sleep(100) IS NULL",
+                Some(RawQueryError::Function("pg_sleep".to_string())),
+            ),
+            (
+                "SELECT email, name FROM user_stats LIMIT (SELECT 1)",
+                Some(RawQueryError::Limit("(SELECT 1)".to_string())),
+            ),
+        ];
+
+        for (sql, expected) in cases {
+            let err = sanitize(sql).unwrap_err();
+            match expected {
+                Some(expected) => assert_eq!(err, expected, "{sql}"),
+                None => assert!(matches!(err, RawQueryError::Parse(_)), "{sql}"),
+            }
+        }
+    }
+}
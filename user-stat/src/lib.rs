@@ -4,10 +4,12 @@ use anyhow::Result;
 use futures::Stream;
 use pb::{
     user_stats_server::{UserStats, UserStatsServer},
-    QueryRequest, RawQueryRequest, User,
+    ActivityEvent, CountResponse, IngestResponse, QueryPageRequest, QueryPageResponse,
+    QueryRequest, RawQueryRequest, RecordNotificationRequest, RecordResponse, RecordVisitRequest,
+    RecordWatchRequest, UpsertUserRequest, User,
 };
 use sqlx::PgPool;
-use tonic::{async_trait, Request, Response, Status};
+use tonic::{async_trait, Request, Response, Status, Streaming};
 
 pub use config::AppConfig;
 
@@ -47,6 +49,75 @@ impl UserStats for UserStatsService {
         let query = request.into_inner();
         self.raw_query(query).await
     }
+
+    async fn count(&self, request: Request<QueryRequest>) -> ServiceResult<CountResponse> {
+        let query = request.into_inner();
+        self.count(query).await
+    }
+
+    async fn query_page(
+        &self,
+        request: Request<QueryPageRequest>,
+    ) -> ServiceResult<QueryPageResponse> {
+        let req = request.into_inner();
+        self.query_page(req).await
+    }
+
+    async fn upsert_user(
+        &self,
+        request: Request<UpsertUserRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.upsert_user(req).await
+    }
+
+    async fn record_visit(
+        &self,
+        request: Request<RecordVisitRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.record_visit(req).await
+    }
+
+    async fn record_view(
+        &self,
+        request: Request<RecordWatchRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.record_view(req).await
+    }
+
+    async fn record_watch_started(
+        &self,
+        request: Request<RecordWatchRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.record_watch_started(req).await
+    }
+
+    async fn record_watch_finished(
+        &self,
+        request: Request<RecordWatchRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.record_watch_finished(req).await
+    }
+
+    async fn record_notification(
+        &self,
+        request: Request<RecordNotificationRequest>,
+    ) -> ServiceResult<RecordResponse> {
+        let req = request.into_inner();
+        self.record_notification(req).await
+    }
+
+    async fn ingest(
+        &self,
+        request: Request<Streaming<ActivityEvent>>,
+    ) -> ServiceResult<IngestResponse> {
+        let events = request.into_inner();
+        self.ingest(events).await
+    }
 }
 
 impl UserStatsService {
@@ -75,7 +146,7 @@ impl Deref for UserStatsService {
 
 #[cfg(feature = "test_utils")]
 pub mod test_utils {
-    use crate::pb::{IdQuery, TimeQuery};
+    use crate::pb::{IdMatch, IdQuery, TimeQuery};
     use crate::{AppConfig, UserStatsService, UserStatsServiceInner};
     use anyhow::Result;
     use chrono::{Duration, Utc};
@@ -123,7 +194,14 @@ pub mod test_utils {
     }
 
     pub fn id(id: &[u32]) -> IdQuery {
-        IdQuery { ids: id.to_vec() }
+        id_match(id, IdMatch::Unspecified)
+    }
+
+    pub fn id_match(id: &[u32], mode: IdMatch) -> IdQuery {
+        IdQuery {
+            ids: id.to_vec(),
+            mode: mode as _,
+        }
     }
 
     pub fn tq(lower: Option<i64>, upper: Option<i64>) -> TimeQuery {
@@ -1,7 +1,7 @@
 // This file is @generated by prost-build.
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(sqlx::FromRow, derive_builder::Builder)]
+#[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct User {
@@ -11,6 +11,37 @@ pub struct User {
     #[prost(string, tag = "2")]
     #[builder(setter(into))]
     pub name: ::prost::alloc::string::String,
+    /// content ids the user started but has not finished yet
+    #[prost(uint32, repeated, tag = "3")]
+    #[builder(default)]
+    pub started_but_not_finished: ::prost::alloc::vec::Vec<u32>,
+    /// phone number for sms, empty if unknown
+    #[prost(string, tag = "4")]
+    #[builder(default, setter(into))]
+    pub phone: ::prost::alloc::string::String,
+    /// device id for in-app messages, empty if unknown
+    #[prost(string, tag = "5")]
+    #[builder(default, setter(into))]
+    pub device_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "Gender", tag = "6")]
+    #[builder(default)]
+    pub gender: i32,
+    /// content ids the user watched recently, the most recent first
+    #[prost(uint32, repeated, tag = "7")]
+    #[builder(default)]
+    pub recent_watched: ::prost::alloc::vec::Vec<u32>,
+    /// content ids the user viewed but has not started yet
+    #[prost(uint32, repeated, tag = "8")]
+    #[builder(default)]
+    pub viewed_but_not_started: ::prost::alloc::vec::Vec<u32>,
+    /// content ids the user has finished
+    #[prost(uint32, repeated, tag = "9")]
+    #[builder(default)]
+    pub finished: ::prost::alloc::vec::Vec<u32>,
+    /// language tag such as zh-TW, empty if unknown
+    #[prost(string, tag = "10")]
+    #[builder(default, setter(into))]
+    pub locale: ::prost::alloc::string::String,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -23,6 +54,10 @@ pub struct QueryRequest {
     #[prost(map = "string, message", tag = "2")]
     #[builder(setter(each(name = "id", into)))]
     pub ids: ::std::collections::HashMap<::prost::alloc::string::String, IdQuery>,
+    /// combined with the conditions above using AND
+    #[prost(message, optional, tag = "3")]
+    #[builder(default, setter(into, strip_option))]
+    pub filter: ::core::option::Option<Filter>,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -32,6 +67,36 @@ pub struct RawQueryRequest {
     #[builder(setter(into))]
     pub query: ::prost::alloc::string::String,
 }
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CountResponse {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryPageRequest {
+    #[prost(message, optional, tag = "1")]
+    #[builder(default, setter(into, strip_option))]
+    pub query: ::core::option::Option<QueryRequest>,
+    /// number of users in a page, 100 if not set, at most 1000
+    #[prost(uint32, tag = "2")]
+    #[builder(default)]
+    pub page_size: u32,
+    /// next_cursor of the previous page, empty for the first page
+    #[prost(string, tag = "3")]
+    #[builder(default, setter(into))]
+    pub cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryPageResponse {
+    /// users ordered by email
+    #[prost(message, repeated, tag = "1")]
+    pub users: ::prost::alloc::vec::Vec<User>,
+    /// empty if there are no more pages
+    #[prost(string, tag = "2")]
+    pub next_cursor: ::prost::alloc::string::String,
+}
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
@@ -41,12 +106,320 @@ pub struct TimeQuery {
     #[prost(message, optional, tag = "2")]
     pub upper: ::core::option::Option<::prost_types::Timestamp>,
 }
+/// an empty list of ids matches all users regardless of the mode
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct IdQuery {
     #[prost(uint32, repeated, tag = "1")]
     pub ids: ::prost::alloc::vec::Vec<u32>,
+    #[prost(enumeration = "IdMatch", tag = "2")]
+    #[builder(default)]
+    pub mode: i32,
+}
+/// a boolean expression over user_stats columns, an empty filter matches all users
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Filter {
+    #[prost(oneof = "filter::Expr", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub expr: ::core::option::Option<filter::Expr>,
+}
+/// Nested message and enum types in `Filter`.
+pub mod filter {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Expr {
+        /// all of the filters must match
+        #[prost(message, tag = "1")]
+        And(super::FilterList),
+        /// any of the filters must match
+        #[prost(message, tag = "2")]
+        Or(super::FilterList),
+        /// the filter must not match, NULL is treated as not matched
+        #[prost(message, tag = "3")]
+        Not(::prost::alloc::boxed::Box<super::Filter>),
+        #[prost(message, tag = "4")]
+        Time(super::TimeFilter),
+        #[prost(message, tag = "5")]
+        Ids(super::IdFilter),
+        #[prost(message, tag = "6")]
+        Gender(super::GenderFilter),
+        #[prost(message, tag = "7")]
+        Null(super::NullFilter),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilterList {
+    #[prost(message, repeated, tag = "1")]
+    pub filters: ::prost::alloc::vec::Vec<Filter>,
+}
+/// timestamp column is within the range, bounds are inclusive
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TimeFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub lower: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    pub upper: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IdFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+    #[prost(enumeration = "IdOperator", tag = "2")]
+    pub op: i32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub ids: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GenderFilter {
+    #[prost(enumeration = "Gender", tag = "1")]
+    pub gender: i32,
+}
+/// column is NULL, id and contact columns are also treated as NULL when they are empty
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NullFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+}
+/// create the user, or update the name, gender and contacts of an existing one
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpsertUserRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub email: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    #[builder(setter(into))]
+    pub name: ::prost::alloc::string::String,
+    /// unspecified keeps the current gender, or unknown for a new user
+    #[prost(enumeration = "Gender", tag = "3")]
+    #[builder(default)]
+    pub gender: i32,
+    /// empty keeps the current phone number
+    #[prost(string, tag = "4")]
+    #[builder(default, setter(into))]
+    pub phone: ::prost::alloc::string::String,
+    /// empty keeps the current device id
+    #[prost(string, tag = "5")]
+    #[builder(default, setter(into))]
+    pub device_id: ::prost::alloc::string::String,
+    /// language tag such as zh-TW, empty keeps the current locale
+    #[prost(string, tag = "6")]
+    #[builder(default, setter(into))]
+    pub locale: ::prost::alloc::string::String,
+}
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordVisitRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub email: ::prost::alloc::string::String,
+    /// when the event happened, now if not set
+    #[prost(message, optional, tag = "2")]
+    #[builder(default, setter(into, strip_option))]
+    pub at: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// the user viewed, started or finished watching a content
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordWatchRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub email: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub content_id: u32,
+    /// when the event happened, now if not set
+    #[prost(message, optional, tag = "3")]
+    #[builder(default, setter(into, strip_option))]
+    pub at: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// a notification has been delivered to the user
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordNotificationRequest {
+    #[prost(string, tag = "1")]
+    #[builder(setter(into))]
+    pub email: ::prost::alloc::string::String,
+    #[prost(enumeration = "NotificationChannel", tag = "2")]
+    #[builder(default)]
+    pub channel: i32,
+    /// when the notification was sent, now if not set
+    #[prost(message, optional, tag = "3")]
+    #[builder(default, setter(into, strip_option))]
+    pub at: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RecordResponse {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ActivityEvent {
+    #[prost(oneof = "activity_event::Event", tags = "1, 2, 3, 4, 5, 6")]
+    pub event: ::core::option::Option<activity_event::Event>,
+}
+/// Nested message and enum types in `ActivityEvent`.
+pub mod activity_event {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "1")]
+        UpsertUser(super::UpsertUserRequest),
+        #[prost(message, tag = "2")]
+        Visit(super::RecordVisitRequest),
+        #[prost(message, tag = "3")]
+        View(super::RecordWatchRequest),
+        #[prost(message, tag = "4")]
+        WatchStarted(super::RecordWatchRequest),
+        #[prost(message, tag = "5")]
+        WatchFinished(super::RecordWatchRequest),
+        #[prost(message, tag = "6")]
+        Notification(super::RecordNotificationRequest),
+    }
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct IngestResponse {
+    /// events which have been applied
+    #[prost(uint64, tag = "1")]
+    pub accepted: u64,
+    /// invalid events, or events of unknown users
+    #[prost(uint64, tag = "2")]
+    pub rejected: u64,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum IdMatch {
+    /// same as ID_MATCH_ALL
+    Unspecified = 0,
+    /// column contains all of the ids (@>)
+    All = 1,
+    /// column contains any of the ids (&&)
+    Any = 2,
+    /// column contains none of the ids, users without any ids included
+    None = 3,
+}
+impl IdMatch {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ID_MATCH_UNSPECIFIED",
+            Self::All => "ID_MATCH_ALL",
+            Self::Any => "ID_MATCH_ANY",
+            Self::None => "ID_MATCH_NONE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ID_MATCH_UNSPECIFIED" => Some(Self::Unspecified),
+            "ID_MATCH_ALL" => Some(Self::All),
+            "ID_MATCH_ANY" => Some(Self::Any),
+            "ID_MATCH_NONE" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Gender {
+    Unspecified = 0,
+    Female = 1,
+    Male = 2,
+    Unknown = 3,
+}
+impl Gender {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "GENDER_UNSPECIFIED",
+            Self::Female => "GENDER_FEMALE",
+            Self::Male => "GENDER_MALE",
+            Self::Unknown => "GENDER_UNKNOWN",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GENDER_UNSPECIFIED" => Some(Self::Unspecified),
+            "GENDER_FEMALE" => Some(Self::Female),
+            "GENDER_MALE" => Some(Self::Male),
+            "GENDER_UNKNOWN" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum IdOperator {
+    Unspecified = 0,
+    /// column contains all of the ids (@>)
+    Contains = 1,
+    /// column contains any of the ids (&&)
+    Overlaps = 2,
+    /// every id in the column is one of the ids (<@)
+    ContainedBy = 3,
+}
+impl IdOperator {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ID_OPERATOR_UNSPECIFIED",
+            Self::Contains => "ID_OPERATOR_CONTAINS",
+            Self::Overlaps => "ID_OPERATOR_OVERLAPS",
+            Self::ContainedBy => "ID_OPERATOR_CONTAINED_BY",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ID_OPERATOR_UNSPECIFIED" => Some(Self::Unspecified),
+            "ID_OPERATOR_CONTAINS" => Some(Self::Contains),
+            "ID_OPERATOR_OVERLAPS" => Some(Self::Overlaps),
+            "ID_OPERATOR_CONTAINED_BY" => Some(Self::ContainedBy),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum NotificationChannel {
+    Unspecified = 0,
+    Email = 1,
+    Sms = 2,
+    InApp = 3,
+}
+impl NotificationChannel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "NOTIFICATION_CHANNEL_UNSPECIFIED",
+            Self::Email => "NOTIFICATION_CHANNEL_EMAIL",
+            Self::Sms => "NOTIFICATION_CHANNEL_SMS",
+            Self::InApp => "NOTIFICATION_CHANNEL_IN_APP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "NOTIFICATION_CHANNEL_UNSPECIFIED" => Some(Self::Unspecified),
+            "NOTIFICATION_CHANNEL_EMAIL" => Some(Self::Email),
+            "NOTIFICATION_CHANNEL_SMS" => Some(Self::Sms),
+            "NOTIFICATION_CHANNEL_IN_APP" => Some(Self::InApp),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod user_stats_client {
@@ -168,6 +541,142 @@ pub mod user_stats_client {
                 .insert(GrpcMethod::new("user_stats.UserStats", "RawQuery"));
             self.inner.server_streaming(req, path, codec).await
         }
+        pub async fn count(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Count");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Count"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn query_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryPageRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/QueryPage");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "QueryPage"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn upsert_user(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpsertUserRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/UpsertUser");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "UpsertUser"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn record_visit(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordVisitRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/RecordVisit");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "RecordVisit"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn record_view(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/RecordView");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "RecordView"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn record_watch_started(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/user_stats.UserStats/RecordWatchStarted");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                "user_stats.UserStats",
+                "RecordWatchStarted",
+            ));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn record_watch_finished(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/user_stats.UserStats/RecordWatchFinished");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                "user_stats.UserStats",
+                "RecordWatchFinished",
+            ));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn record_notification(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordNotificationRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/user_stats.UserStats/RecordNotification");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                "user_stats.UserStats",
+                "RecordNotification",
+            ));
+            self.inner.unary(req, path, codec).await
+        }
+        /// bulk ingestion, events are applied in order
+        pub async fn ingest(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::ActivityEvent>,
+        ) -> std::result::Result<tonic::Response<super::IngestResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Ingest");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Ingest"));
+            self.inner.client_streaming(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -201,6 +710,43 @@ pub mod user_stats_server {
             &self,
             request: tonic::Request<super::RawQueryRequest>,
         ) -> std::result::Result<tonic::Response<Self::RawQueryStream>, tonic::Status>;
+        async fn count(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status>;
+        async fn query_page(
+            &self,
+            request: tonic::Request<super::QueryPageRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status>;
+        async fn upsert_user(
+            &self,
+            request: tonic::Request<super::UpsertUserRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        async fn record_visit(
+            &self,
+            request: tonic::Request<super::RecordVisitRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        async fn record_view(
+            &self,
+            request: tonic::Request<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        async fn record_watch_started(
+            &self,
+            request: tonic::Request<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        async fn record_watch_finished(
+            &self,
+            request: tonic::Request<super::RecordWatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        async fn record_notification(
+            &self,
+            request: tonic::Request<super::RecordNotificationRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordResponse>, tonic::Status>;
+        /// bulk ingestion, events are applied in order
+        async fn ingest(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::ActivityEvent>>,
+        ) -> std::result::Result<tonic::Response<super::IngestResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct UserStatsServer<T> {
@@ -356,6 +902,357 @@ pub mod user_stats_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_stats.UserStats/Count" => {
+                    #[allow(non_camel_case_types)]
+                    struct CountSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for CountSvc<T> {
+                        type Response = super::CountResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as UserStats>::count(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/QueryPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct QueryPageSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryPageRequest> for QueryPageSvc<T> {
+                        type Response = super::QueryPageResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryPageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::query_page(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = QueryPageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/UpsertUser" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpsertUserSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::UpsertUserRequest> for UpsertUserSvc<T> {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpsertUserRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::upsert_user(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpsertUserSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/RecordVisit" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecordVisitSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::RecordVisitRequest> for RecordVisitSvc<T> {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordVisitRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::record_visit(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecordVisitSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/RecordView" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecordViewSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::RecordWatchRequest> for RecordViewSvc<T> {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordWatchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::record_view(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecordViewSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/RecordWatchStarted" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecordWatchStartedSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::RecordWatchRequest>
+                        for RecordWatchStartedSvc<T>
+                    {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordWatchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::record_watch_started(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecordWatchStartedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/RecordWatchFinished" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecordWatchFinishedSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::RecordWatchRequest>
+                        for RecordWatchFinishedSvc<T>
+                    {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordWatchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::record_watch_finished(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecordWatchFinishedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/RecordNotification" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecordNotificationSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::RecordNotificationRequest>
+                        for RecordNotificationSvc<T>
+                    {
+                        type Response = super::RecordResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordNotificationRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::record_notification(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecordNotificationSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Ingest" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::ClientStreamingService<super::ActivityEvent> for IngestSvc<T> {
+                        type Response = super::IngestResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::ActivityEvent>>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::ingest(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IngestSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.client_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     let mut response = http::Response::new(empty_body());
                     let headers = response.headers_mut();
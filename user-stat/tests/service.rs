@@ -1,12 +1,17 @@
 use anyhow::Result;
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use sqlx_db_tester::TestPg;
 use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::time::sleep;
 use tonic::transport::Server;
+use tonic::Code;
+use user_stat::pb::activity_event::Event;
 use user_stat::pb::user_stats_client::UserStatsClient;
-use user_stat::pb::{QueryRequestBuilder, RawQueryRequestBuilder};
+use user_stat::pb::{
+    ActivityEvent, QueryPageRequestBuilder, QueryRequestBuilder, RawQueryRequestBuilder,
+    RecordVisitRequestBuilder, RecordWatchRequestBuilder, UpsertUserRequestBuilder,
+};
 use user_stat::test_utils::{id, tq};
 use user_stat::UserStatsService;
 
@@ -31,6 +36,21 @@ async fn raw_query_could_work() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn raw_query_should_reject_writes() -> Result<()> {
+    let (_tdb, addr) = start_server(PORT_BASE + 2).await?;
+    let mut client = UserStatsClient::connect(format!("http://{addr}")).await?;
+    let req = RawQueryRequestBuilder::default()
+        .query("DELETE FROM user_stats")
+        .build()?;
+
+    let err = client.raw_query(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert!(err.message().contains("only SELECT statements are allowed"));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn query_could_work() -> Result<()> {
     let (_tdb, addr) = start_server(PORT_BASE + 1).await?;
@@ -49,6 +69,83 @@ async fn query_could_work() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn count_and_query_page_could_work() -> Result<()> {
+    let (_tdb, addr) = start_server(PORT_BASE + 3).await?;
+    let mut client = UserStatsClient::connect(format!("http://{addr}")).await?;
+    let query = QueryRequestBuilder::default()
+        .timestamp(("created_at".to_string(), tq(Some(120), None)))
+        .build()?;
+
+    let count = client.count(query.clone()).await?.into_inner().count;
+
+    let req = QueryPageRequestBuilder::default()
+        .query(query)
+        .page_size(count as u32 + 1)
+        .build()?;
+    let page = client.query_page(req).await?.into_inner();
+    assert_eq!(page.users.len() as u64, count);
+    assert!(page.next_cursor.is_empty());
+
+    let req = QueryPageRequestBuilder::default()
+        .cursor("not a cursor!")
+        .build()?;
+    let err = client.query_page(req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ingest_could_work() -> Result<()> {
+    let (_tdb, addr) = start_server(PORT_BASE + 4).await?;
+    let mut client = UserStatsClient::connect(format!("http://{addr}")).await?;
+    let email = "ingest@acme.org";
+    let watch = |content_id: u32| {
+        RecordWatchRequestBuilder::default()
+            .email(email)
+            .content_id(content_id)
+            .build()
+    };
+    let events = vec![
+        Event::UpsertUser(
+            UpsertUserRequestBuilder::default()
+                .email(email)
+                .name("Ingest")
+                .build()?,
+        ),
+        Event::Visit(RecordVisitRequestBuilder::default().email(email).build()?),
+        Event::WatchStarted(watch(42)?),
+        Event::WatchStarted(watch(43)?),
+        Event::WatchFinished(watch(42)?),
+        Event::Visit(
+            RecordVisitRequestBuilder::default()
+                .email("nobody@acme.org")
+                .build()?,
+        ),
+    ];
+    let events = events.into_iter().map(|e| ActivityEvent { event: Some(e) });
+
+    let resp = client.ingest(stream::iter(events)).await?.into_inner();
+    assert_eq!(resp.accepted, 5);
+    assert_eq!(resp.rejected, 1);
+
+    let query = QueryRequestBuilder::default()
+        .id(("finished".to_string(), id(&[42])))
+        .id(("started_but_not_finished".to_string(), id(&[43])))
+        .build()?;
+    let users = client
+        .query(query)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].as_ref().unwrap().started_but_not_finished, [43]);
+
+    Ok(())
+}
+
 async fn start_server(port: u32) -> Result<(TestPg, SocketAddr)> {
     let addr = format!("[::1]:{}", port).parse()?;
 